@@ -1,9 +1,105 @@
-use miette::Diagnostic;
+use miette::{Diagnostic, SourceSpan};
 use thiserror::Error;
 
 #[derive(Clone, Debug, Error, Diagnostic)]
 pub enum IlgiError {
-
+    #[error("{path} does not start with a front matter block")]
+    #[diagnostic(code(ilgi::content::front_matter), help("articles must begin with TOML front matter delimited by `+++` lines"))]
+    MissingFrontMatter {
+        path: String,
+    },
+    #[error("theme translation catalog {path} is invalid: {message}")]
+    #[diagnostic(code(ilgi::theme::translations))]
+    InvalidTranslationCatalog {
+        path: String,
+        message: String,
+    },
+    #[error("unknown time zone {name}")]
+    #[diagnostic(code(ilgi::config::timezone), help("use an IANA time zone name such as `UTC` or `Asia/Seoul`"))]
+    UnknownTimezone {
+        name: String,
+    },
+    #[error("theme highlighting file {path} is invalid: {message}")]
+    #[diagnostic(code(ilgi::theme::highlight))]
+    InvalidHighlightDefinition {
+        path: String,
+        message: String,
+    },
+    #[error("unknown highlighting theme {name}")]
+    #[diagnostic(code(ilgi::config::highlight_theme), help("available themes: {available}"))]
+    UnknownHighlightTheme {
+        name: String,
+        available: String,
+    },
+    #[error("invalid math in {path} on line {line}: {message}")]
+    #[diagnostic(code(ilgi::content::math))]
+    InvalidMath {
+        path: String,
+        line: usize,
+        message: String,
+        #[source_code]
+        document: String,
+        #[label("{message}")]
+        span: SourceSpan,
+    },
+    #[error("{path} links to {target}, which does not exist")]
+    #[diagnostic(code(ilgi::content::link), help("link to another article by its path in the content directory, such as `@/posts/hello.md`, or by its slug"))]
+    BrokenLink {
+        path: String,
+        target: String,
+    },
+    #[error("{path} links to #{fragment} in {target}, which has no element with that id")]
+    #[diagnostic(code(ilgi::content::link_fragment))]
+    BrokenLinkFragment {
+        path: String,
+        target: String,
+        fragment: String,
+    },
+    #[error("invalid redirect from {from}: {message}")]
+    #[diagnostic(code(ilgi::content::redirect))]
+    InvalidRedirect {
+        from: String,
+        message: String,
+    },
+    #[error("invalid permalink for {path}: {message}")]
+    #[diagnostic(code(ilgi::config::permalink), help("permalink patterns may use :year, :month, :day, :slug, :title, :section, :path and :lang"))]
+    InvalidPermalink {
+        path: String,
+        message: String,
+    },
+    #[error("{url} is generated by more than one page: {sources}")]
+    #[diagnostic(code(ilgi::content::duplicate_url), help("give one of the pages a different `slug` or permalink pattern"))]
+    DuplicateUrl {
+        url: String,
+        sources: String,
+    },
+    #[error("{taxonomy} term \"{term}\" has no letters or digits to make a URL slug from")]
+    #[diagnostic(code(ilgi::content::term_slug), help("rename the term, or set build.permalinks.slugify to `unicode` if its script cannot be transliterated"))]
+    EmptyTermSlug {
+        taxonomy: String,
+        term: String,
+    },
+    #[error("{taxonomy} terms \"{existing}\" and \"{term}\" both have the slug \"{slug}\"")]
+    #[diagnostic(code(ilgi::content::term_slug_collision), help("rename one of the terms so their URLs differ"))]
+    DuplicateTermSlug {
+        taxonomy: String,
+        existing: String,
+        term: String,
+        slug: String,
+    },
+    #[error("found {count} broken links")]
+    #[diagnostic(code(ilgi::check::links))]
+    BrokenLinks {
+        count: usize,
+    },
+    #[error("no database is configured")]
+    #[diagnostic(code(ilgi::db::url), help("set database.url in the config file or the ILGI_DATABASE_URL environment variable"))]
+    MissingDatabaseUrl,
+    #[error("the database has {count} pending migrations")]
+    #[diagnostic(code(ilgi::db::migrations), help("run `ilgi db migrate` to apply them"))]
+    PendingMigrations {
+        count: usize,
+    },
 }
 
-pub type IResult<T> = miette::Result<T>;
\ No newline at end of file
+pub type IResult<T> = miette::Result<T>;
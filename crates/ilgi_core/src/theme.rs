@@ -12,6 +12,7 @@ pub struct ThemeDefinition {
     pub compatibility: Option<Version>,
     pub license: Option<String>,
     pub description: Option<String>,
+    pub default_language: Option<String>,
     pub homepage: Option<String>,
     pub categories: Option<Vec<String>>,
     pub tags: Option<Vec<String>>
@@ -0,0 +1,545 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::Utc;
+use dashmap::DashMap;
+use miette::{IntoDiagnostic, WrapErr};
+use rayon::prelude::*;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, Semaphore};
+use tokio::task::JoinSet;
+use tokio::time::{sleep_until, Instant};
+use tracing::{info, instrument, warn};
+use url::Url;
+use ilgi_core::error::IResult;
+use crate::config::{IlgiConfig, LinkCheck};
+use crate::file_ops::load_dir;
+use crate::sitebuild::article::Article;
+use crate::sitebuild::percent_decode;
+use crate::sitebuild::render::output_file;
+use crate::sitebuild::summary::decode_entities;
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LinkProblem {
+    pub file: String,
+    pub line: usize,
+    pub url: String,
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Reference {
+    line: usize,
+    url: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+struct ScannedPage {
+    ids: HashSet<String>,
+    references: Vec<Reference>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Target {
+    Internal { path: String, fragment: Option<String> },
+    External(Url),
+    Skip,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct LinkCache {
+    checked: HashMap<String, i64>,
+}
+
+type Attributes = Vec<(String, String)>;
+
+fn parse_tag(html: &str, start: usize) -> Option<(String, Attributes, usize)> {
+    let bytes = html.as_bytes();
+    let mut index = start + 1;
+    if !bytes.get(index)?.is_ascii_alphabetic() {
+        return None;
+    }
+    while bytes.get(index).is_some_and(|c| c.is_ascii_alphanumeric() || *c == b'-') {
+        index += 1;
+    }
+    let name = html[start + 1..index].to_ascii_lowercase();
+
+    let mut attributes = vec![];
+    loop {
+        while bytes.get(index).is_some_and(|c| c.is_ascii_whitespace() || *c == b'/') {
+            index += 1;
+        }
+        if *bytes.get(index)? == b'>' {
+            return Some((name, attributes, index + 1));
+        }
+        let attribute_start = index;
+        while bytes.get(index).is_some_and(|c| !c.is_ascii_whitespace() && !b"=>/".contains(c)) {
+            index += 1;
+        }
+        let attribute = html[attribute_start..index].to_ascii_lowercase();
+        while bytes.get(index).is_some_and(u8::is_ascii_whitespace) {
+            index += 1;
+        }
+        if bytes.get(index) != Some(&b'=') {
+            attributes.push((attribute, String::new()));
+            continue;
+        }
+        index += 1;
+        while bytes.get(index).is_some_and(u8::is_ascii_whitespace) {
+            index += 1;
+        }
+        let value = match bytes.get(index)? {
+            quote @ (b'"' | b'\'') => {
+                let end = index + 1 + html[index + 1..].find(*quote as char)?;
+                let value = &html[index + 1..end];
+                index = end + 1;
+                value
+            }
+            _ => {
+                let value_start = index;
+                while bytes.get(index).is_some_and(|c| !c.is_ascii_whitespace() && *c != b'>') {
+                    index += 1;
+                }
+                &html[value_start..index]
+            }
+        };
+        attributes.push((attribute, decode_entities(value)));
+    }
+}
+
+fn scan_html(html: &str) -> ScannedPage {
+    let mut page = ScannedPage::default();
+    let mut line = 1;
+    let mut counted = 0;
+    let mut index = 0;
+    while let Some(found) = html[index..].find('<') {
+        let start = index + found;
+        if html[start..].starts_with("<!--") {
+            index = html[start..].find("-->").map_or(html.len(), |end| start + end + 3);
+            continue;
+        }
+        let Some((name, attributes, end)) = parse_tag(html, start) else {
+            index = start + 1;
+            continue
+        };
+        line += html[counted..start].matches('\n').count();
+        counted = start;
+
+        let rel = attributes.iter().find(|(attribute, _)| attribute == "rel").map(|(_, value)| value.as_str()).unwrap_or_default();
+        for (attribute, value) in &attributes {
+            match attribute.as_str() {
+                "id" => {
+                    page.ids.insert(value.clone());
+                }
+                "name" if name == "a" => {
+                    page.ids.insert(value.clone());
+                }
+                "href" if rel.contains("preconnect") || rel.contains("dns-prefetch") => {}
+                "href" | "src" | "poster" => page.references.push(Reference { line, url: value.clone() }),
+                "srcset" => page.references.extend(
+                    value.split(',')
+                        .filter_map(|candidate| candidate.split_whitespace().next())
+                        .map(|url| Reference { line, url: url.to_string() })
+                ),
+                _ => {}
+            }
+        }
+
+        index = end;
+        if name == "script" || name == "style" {
+            let close = format!("</{name}");
+            index = html[end..].to_ascii_lowercase().find(&close).map_or(html.len(), |close| end + close);
+        }
+    }
+    page
+}
+
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false
+    };
+    let parts = parts.collect::<Vec<_>>();
+    for (index, part) in parts.iter().enumerate() {
+        if index == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(found) => rest = &rest[found + part.len()..],
+            None => return false,
+        }
+    }
+    rest.is_empty()
+}
+
+fn is_ignored(config: &LinkCheck, url: &str) -> bool {
+    config.ignore.iter().any(|pattern| match pattern.contains('*') {
+        true => glob_match(pattern, url),
+        false => url.starts_with(pattern.as_str()),
+    })
+}
+
+fn classify(site: &Url, page: &Url, href: &str) -> Result<Target, String> {
+    let href = href.trim();
+    if href.is_empty() {
+        return Ok(Target::Skip);
+    }
+    let url = page.join(href).map_err(|why| format!("invalid URL: {why}"))?;
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Ok(Target::Skip);
+    }
+    if url.origin() == site.origin() && url.path().starts_with(site.path()) {
+        return Ok(Target::Internal {
+            path: percent_decode(&url.path()[site.path().len() - 1..]),
+            fragment: url.fragment().map(percent_decode).filter(|fragment| !fragment.is_empty()),
+        });
+    }
+    Ok(Target::External(url))
+}
+
+fn resolve_internal<'a>(pages: &'a HashMap<String, ScannedPage>, files: &HashSet<String>, path: &str) -> Option<(String, Option<&'a ScannedPage>)> {
+    let mut candidates = vec![path.to_string()];
+    match path.ends_with('/') {
+        true => candidates.push(format!("{path}index.html")),
+        false => candidates.extend([format!("{path}/index.html"), format!("{path}.html")]),
+    }
+    candidates.into_iter()
+        .find(|candidate| files.contains(candidate))
+        .map(|found| {
+            let page = pages.get(&found);
+            (found, page)
+        })
+}
+
+async fn fetch(client: &Client, url: Url) -> Result<(), String> {
+    let response = client.head(url.clone()).send().await.map_err(|why| why.to_string())?;
+    let status = match response.status() {
+        StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED | StatusCode::FORBIDDEN => {
+            client.get(url).send().await.map_err(|why| why.to_string())?.status()
+        }
+        status => status,
+    };
+    match status.is_success() {
+        true => Ok(()),
+        false => Err(format!("responded with {status}")),
+    }
+}
+
+async fn load_cache(path: &Path) -> LinkCache {
+    match tokio::fs::read(path).await {
+        Ok(data) => serde_json::from_slice(&data).unwrap_or_else(|why| {
+            warn!("ignoring unreadable link cache {}: {why}", path.display());
+            LinkCache::default()
+        }),
+        Err(_) => LinkCache::default(),
+    }
+}
+
+#[instrument(skip(config, urls))]
+async fn check_external(config: &LinkCheck, urls: BTreeSet<Url>) -> IResult<HashMap<String, String>> {
+    let cache_path = Path::new(&config.cache_file);
+    let mut cache = load_cache(cache_path).await;
+    let now = Utc::now().timestamp();
+    let fresh_after = now - config.cache_hours as i64 * 3600;
+    let pending = urls.into_iter()
+        .filter(|url| cache.checked.get(url.as_str()).is_none_or(|checked| *checked < fresh_after))
+        .collect::<Vec<_>>();
+    info!("checking {} external links", pending.len());
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(config.timeout_secs))
+        .user_agent(concat!("ilgi/", env!("CARGO_PKG_VERSION"), " link checker"))
+        .build()
+        .into_diagnostic()?;
+    let permits = Arc::new(Semaphore::new(config.concurrency.max(1)));
+    let domains = Arc::new(DashMap::<String, Arc<Mutex<Option<Instant>>>>::new());
+    let interval = Duration::from_millis(config.domain_interval_ms);
+
+    let mut tasks = JoinSet::new();
+    for url in pending {
+        let (client, permits, domains) = (client.clone(), permits.clone(), domains.clone());
+        tasks.spawn(async move {
+            let domain = domains.entry(url.host_str().unwrap_or_default().to_string()).or_default().clone();
+            {
+                let mut last = domain.lock().await;
+                if let Some(last) = *last {
+                    sleep_until(last + interval).await;
+                }
+                *last = Some(Instant::now());
+            }
+            let _permit = permits.acquire_owned().await;
+            let result = fetch(&client, url.clone()).await;
+            (url, result)
+        });
+    }
+
+    let mut failures = HashMap::new();
+    while let Some(joined) = tasks.join_next().await {
+        match joined.into_diagnostic()? {
+            (url, Ok(())) => {
+                cache.checked.insert(url.to_string(), now);
+            }
+            (url, Err(why)) => {
+                cache.checked.remove(url.as_str());
+                failures.insert(url.to_string(), why);
+            }
+        }
+    }
+
+    if let Some(parent) = cache_path.parent() {
+        tokio::fs::create_dir_all(parent).await.into_diagnostic()?;
+    }
+    tokio::fs::write(cache_path, serde_json::to_vec(&cache).into_diagnostic()?)
+        .await
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to write the link cache to {}", cache_path.display()))?;
+    Ok(failures)
+}
+
+pub fn source_files(content_dir: &Path, articles: &[Article]) -> HashMap<String, PathBuf> {
+    articles.iter()
+        .map(|article| (output_file(&article.path), content_dir.join(&article.source)))
+        .collect()
+}
+
+fn locate_sources(problems: &mut [LinkProblem], output: &Path, sources: &HashMap<String, PathBuf>) {
+    let mut contents = HashMap::<&Path, Option<String>>::new();
+    for problem in problems {
+        let located = sources.get(&problem.file).and_then(|source| {
+            let text = contents.entry(source).or_insert_with(|| std::fs::read_to_string(source).ok()).as_deref()?;
+            let line = text.lines().position(|line| line.contains(&problem.url))?;
+            Some((source, line + 1))
+        });
+        match located {
+            Some((source, line)) => {
+                problem.file = source.display().to_string();
+                problem.line = line;
+            }
+            None => problem.file = output.join(problem.file.trim_start_matches('/')).display().to_string(),
+        }
+    }
+}
+
+#[instrument(skip(config, sources))]
+pub async fn check_links(config: &IlgiConfig, output: &Path, sources: &HashMap<String, PathBuf>, offline: bool) -> IResult<Vec<LinkProblem>> {
+    let mut base_url = config.base_url.clone();
+    if !base_url.ends_with('/') {
+        base_url.push('/');
+    }
+    let site = Url::parse(&base_url).into_diagnostic().wrap_err("base_url is not a valid URL")?;
+
+    let files = load_dir(output)?
+        .filter(|path| path.is_file())
+        .filter_map(|path| {
+            let relative = path.strip_prefix(output).ok()?.components()
+                .map(|component| component.as_os_str().to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+                .join("/");
+            Some((format!("/{relative}"), path))
+        })
+        .collect::<HashMap<_, _>>();
+    let pages = files.par_iter()
+        .filter(|(_, path)| path.extension().is_some_and(|ext| ext == "html" || ext == "htm"))
+        .map(|(url, path)| {
+            let html = std::fs::read_to_string(path).into_diagnostic()?;
+            Ok((url.clone(), scan_html(&html)))
+        })
+        .collect::<IResult<HashMap<String, ScannedPage>>>()?;
+    let file_names = files.keys().cloned().collect::<HashSet<_>>();
+
+    let mut problems = vec![];
+    let mut external = HashMap::<Url, Vec<(String, usize, String)>>::new();
+    for (page_path, page) in &pages {
+        let page_url = site.join(page_path.trim_start_matches('/')).into_diagnostic()?;
+        for reference in &page.references {
+            let mut problem = |reason: String| problems.push(LinkProblem {
+                file: page_path.clone(),
+                line: reference.line,
+                url: reference.url.clone(),
+                reason,
+            });
+            if is_ignored(&config.link_check, &reference.url) {
+                continue;
+            }
+            match classify(&site, &page_url, &reference.url) {
+                Err(why) => problem(why),
+                Ok(Target::Skip) => {}
+                Ok(Target::Internal { path, fragment }) => match resolve_internal(&pages, &file_names, &path) {
+                    None => problem(format!("{path} does not exist")),
+                    Some((found, Some(target))) => match fragment {
+                        Some(fragment) if fragment != "top" && !target.ids.contains(&fragment) => {
+                            problem(format!("{found} has no element with id \"{fragment}\""))
+                        }
+                        _ => {}
+                    },
+                    Some(_) => {}
+                },
+                Ok(Target::External(mut url)) => {
+                    url.set_fragment(None);
+                    if !is_ignored(&config.link_check, url.as_str()) {
+                        external.entry(url).or_default().push((page_path.clone(), reference.line, reference.url.clone()));
+                    }
+                }
+            }
+        }
+    }
+
+    match (offline || !config.link_check.external, external.is_empty()) {
+        (true, false) => info!("skipping {} external links", external.len()),
+        (false, false) => {
+            let failures = check_external(&config.link_check, external.keys().cloned().collect()).await?;
+            for (url, sources) in external {
+                if let Some(why) = failures.get(url.as_str()) {
+                    problems.extend(sources.into_iter().map(|(file, line, href)| LinkProblem { file, line, url: href, reason: why.clone() }));
+                }
+            }
+        }
+        (_, true) => {}
+    }
+
+    locate_sources(&mut problems, output, sources);
+    problems.sort();
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use crate::config::test_config;
+    use super::*;
+
+    const OK: &str = "HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n";
+    const NOT_FOUND: &str = "HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\nconnection: close\r\n\r\n";
+    const MOVED: &str = "HTTP/1.1 301 Moved Permanently\r\nlocation: /ok\r\ncontent-length: 0\r\nconnection: close\r\n\r\n";
+
+    async fn stand_in() -> (SocketAddr, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut buffer = [0; 1024];
+                    let read = stream.read(&mut buffer).await.unwrap_or_default();
+                    let request = String::from_utf8_lossy(&buffer[..read]).into_owned();
+                    let response = match request.split_whitespace().nth(1).unwrap_or_default() {
+                        "/ok" => OK,
+                        "/moved" => MOVED,
+                        "/slow" => {
+                            tokio::time::sleep(Duration::from_secs(5)).await;
+                            OK
+                        }
+                        _ => NOT_FOUND,
+                    };
+                    let _ = stream.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        (address, requests)
+    }
+
+    fn link_check(cache: &Path) -> LinkCheck {
+        LinkCheck {
+            external: true,
+            concurrency: 4,
+            domain_interval_ms: 0,
+            timeout_secs: 1,
+            cache_file: cache.display().to_string(),
+            cache_hours: 24,
+            ignore: vec![],
+        }
+    }
+
+    fn config(cache: &Path) -> IlgiConfig {
+        let mut config = test_config();
+        config.link_check = link_check(cache);
+        config
+    }
+
+    fn write_page(output: &Path, path: &str, html: &str) {
+        let file = output.join(path);
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        std::fs::write(file, html).unwrap();
+    }
+
+    #[tokio::test]
+    async fn external_statuses() {
+        let (address, _) = stand_in().await;
+        let directory = tempfile::tempdir().unwrap();
+        let config = link_check(&directory.path().join("cache.json"));
+        let url = |path: &str| Url::parse(&format!("http://{address}{path}")).unwrap();
+
+        let failures = check_external(&config, [url("/ok"), url("/missing"), url("/moved"), url("/slow")].into_iter().collect()).await.unwrap();
+        assert!(!failures.contains_key(url("/ok").as_str()));
+        assert!(!failures.contains_key(url("/moved").as_str()));
+        assert!(failures[url("/missing").as_str()].contains("404"));
+        assert!(failures.contains_key(url("/slow").as_str()));
+
+        let cache = load_cache(Path::new(&config.cache_file)).await;
+        assert!(cache.checked.contains_key(url("/ok").as_str()));
+        assert!(!cache.checked.contains_key(url("/missing").as_str()));
+    }
+
+    #[tokio::test]
+    async fn cached_links_are_not_fetched_again() {
+        let (address, requests) = stand_in().await;
+        let directory = tempfile::tempdir().unwrap();
+        let config = link_check(&directory.path().join("cache.json"));
+        let ok = Url::parse(&format!("http://{address}/ok")).unwrap();
+
+        check_external(&config, [ok.clone()].into_iter().collect()).await.unwrap();
+        let fetched = requests.load(Ordering::SeqCst);
+        assert!(fetched > 0);
+        let failures = check_external(&config, [ok].into_iter().collect()).await.unwrap();
+        assert!(failures.is_empty());
+        assert_eq!(requests.load(Ordering::SeqCst), fetched);
+    }
+
+    #[tokio::test]
+    async fn internal_links_and_offline_mode() {
+        let (address, requests) = stand_in().await;
+        let directory = tempfile::tempdir().unwrap();
+        let config = config(&directory.path().join("cache.json"));
+        let output = directory.path().join("public");
+        write_page(&output, "index.html", &format!(concat!(
+            "<a href=\"/posts/hello/\">ok</a>\n",
+            "<a href=\"/posts/hello/#intro\">anchor</a>\n",
+            "<a href=\"/posts/hello/#missing\">bad anchor</a>\n",
+            "<a href=\"/posts/gone/\">gone</a>\n",
+            "<a href=\"http://{}/missing\">external</a>\n",
+        ), address));
+        write_page(&output, "posts/hello/index.html", "<h2 id=\"intro\">Intro</h2>");
+
+        let problems = check_links(&config, &output, &HashMap::new(), true).await.unwrap();
+        let reported = problems.iter().map(|problem| (problem.line, problem.url.as_str())).collect::<Vec<_>>();
+        assert_eq!(reported, [(3, "/posts/hello/#missing"), (4, "/posts/gone/")]);
+        assert!(problems.iter().all(|problem| problem.file == output.join("index.html").display().to_string()));
+        assert_eq!(requests.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn problems_point_at_markdown_sources() {
+        let directory = tempfile::tempdir().unwrap();
+        let config = config(&directory.path().join("cache.json"));
+        let output = directory.path().join("public");
+        write_page(&output, "posts/hello/index.html", "<header><a href=\"/about/\">about</a></header>\n<p><a href=\"/posts/gone/\">gone</a></p>");
+        let source = directory.path().join("content/posts/hello.md");
+        write_page(directory.path(), "content/posts/hello.md", "+++\ntitle = \"Hello\"\n+++\n\nSee [gone](/posts/gone/).\n");
+        let sources = [("/posts/hello/index.html".to_string(), source.clone())].into_iter().collect();
+
+        let problems = check_links(&config, &output, &sources, true).await.unwrap();
+        let reported = problems.iter().map(|problem| (problem.file.clone(), problem.line)).collect::<Vec<_>>();
+        assert_eq!(reported, [
+            (source.display().to_string(), 5),
+            (output.join("posts/hello/index.html").display().to_string(), 1),
+        ]);
+    }
+}
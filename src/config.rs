@@ -1,27 +1,168 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
+use chrono_tz::Tz;
 use confique::Config;
+use serde::Deserialize;
 use std::default::Default;
+use ilgi_core::error::{IlgiError, IResult};
 
 #[derive(Clone, Debug, PartialEq, Config)]
 pub struct IlgiConfig {
+    pub title: String,
+    pub description: Option<String>,
+    pub base_url: String,
     #[config(default = "en")]
     pub default_language: String,
+    pub languages: Option<Vec<Language>>,
+    #[config(default = "production", env = "ILGI_ENVIRONMENT")]
+    pub environment: String,
+    #[config(default = "UTC")]
+    pub timezone: String,
 
     #[config(nested)]
     pub build: Build,
+    #[config(nested)]
+    pub serve: Serve,
+    #[config(nested)]
+    pub link_check: LinkCheck,
+    #[config(nested)]
+    pub database: Database,
+    #[config(nested)]
+    pub search_api: SearchApi,
 }
 
+impl IlgiConfig {
+    pub fn tz(&self) -> IResult<Tz> {
+        self.timezone.parse::<Tz>()
+            .map_err(|_| IlgiError::UnknownTimezone { name: self.timezone.clone() }.into())
+    }
+
+    pub fn language(&self, code: &str) -> Option<&Language> {
+        self.languages.iter()
+            .flatten()
+            .find(|language| language.code == code)
+    }
+
+    pub fn language_codes(&self) -> Vec<&str> {
+        let mut codes = vec![self.default_language.as_str()];
+        codes.extend(
+            self.languages.iter()
+                .flatten()
+                .map(|language| language.code.as_str())
+                .filter(|code| *code != self.default_language)
+        );
+        codes
+    }
+
+    pub fn is_language(&self, code: &str) -> bool {
+        code == self.default_language || self.language(code).is_some()
+    }
+
+    pub fn language_prefix(&self, code: &str) -> String {
+        match self.language(code).and_then(|language| language.prefix.as_deref()) {
+            Some(prefix) if prefix.trim_matches('/').is_empty() => String::new(),
+            Some(prefix) => format!("/{}", prefix.trim_matches('/')),
+            None if code == self.default_language => String::new(),
+            None => format!("/{code}"),
+        }
+    }
+
+    pub fn language_name<'a>(&'a self, code: &'a str) -> &'a str {
+        self.language(code)
+            .and_then(|language| language.name.as_deref())
+            .unwrap_or(code)
+    }
+
+    pub fn site_title(&self, code: &str) -> &str {
+        self.language(code)
+            .and_then(|language| language.title.as_deref())
+            .unwrap_or(&self.title)
+    }
+
+    pub fn site_description(&self, code: &str) -> Option<&str> {
+        self.language(code)
+            .and_then(|language| language.description.as_deref())
+            .or(self.description.as_deref())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Language {
+    pub code: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub prefix: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Config)]
 pub struct Serve {
     #[config(default = true)]
     pub rss_feed: bool,
     #[config(default = true)]
     pub atom_feed: bool,
+    #[config(default = true)]
+    pub json_feed: bool,
+    #[config(default = 20)]
+    pub feed_limit: usize,
+    #[config(default = false)]
+    pub feed_summaries: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Config)]
+pub struct Database {
+    #[config(env = "ILGI_DATABASE_URL")]
+    pub url: Option<String>,
+    #[config(default = 10)]
+    pub max_connections: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Config)]
+pub struct SearchApi {
+    #[config(default = true)]
+    pub enabled: bool,
+    #[config(default = "/find/")]
+    pub page_path: String,
+    #[config(default = "search")]
+    pub page_template: String,
+    #[config(default = 10)]
+    pub per_page: u64,
+    #[config(default = 50)]
+    pub max_per_page: u64,
+    pub languages: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Config)]
+pub struct LinkCheck {
+    #[config(default = true)]
+    pub external: bool,
+    #[config(default = 8)]
+    pub concurrency: usize,
+    #[config(default = 1000)]
+    pub domain_interval_ms: u64,
+    #[config(default = 15)]
+    pub timeout_secs: u64,
+    #[config(default = ".ilgi/link_cache.json")]
+    pub cache_file: String,
+    #[config(default = 24)]
+    pub cache_hours: u64,
+    #[config(default = [])]
+    pub ignore: Vec<String>,
 }
 
 #[derive(Clone, Debug, PartialEq, Config)]
 pub struct Build {
     #[config(nested)]
     pub git: Git,
+    #[config(default = "content")]
+    pub content_dir: String,
+    #[config(default = false)]
+    pub drafts: bool,
+    #[config(default = false)]
+    pub keep_expired: bool,
     #[config(nested)]
     pub statics: Static,
     #[config(nested)]
@@ -30,9 +171,207 @@ pub struct Build {
     pub javascript: Js,
     #[config(nested)]
     pub css: Css,
+    #[config(nested)]
+    pub permalinks: Permalinks,
+    #[config(nested)]
+    pub markdown: Markdown,
+    #[config(nested)]
+    pub highlight: Highlight,
+    #[config(nested)]
+    pub pagination: Pagination,
+    #[config(nested)]
+    pub archive: Archive,
+    #[config(nested)]
+    pub series: Series,
+    #[config(nested)]
+    pub related: Related,
+    #[config(nested)]
+    pub search: Search,
+    #[config(nested)]
+    pub sitemap: Sitemap,
+    #[config(nested)]
+    pub robots: Robots,
+    #[config(nested)]
+    pub redirects: Redirects,
+    pub taxonomies: Option<Vec<Taxonomy>>,
     pub theme: Option<String>,
 }
 
+impl Build {
+    pub fn taxonomies(&self) -> Vec<Taxonomy> {
+        match &self.taxonomies {
+            Some(taxonomies) => taxonomies.clone(),
+            None => vec![Taxonomy::new("tags"), Taxonomy::new("categories")],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Config)]
+pub struct Pagination {
+    #[config(default = 10)]
+    pub paginate_by: usize,
+    #[config(default = "page/{page}/")]
+    pub path: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Config)]
+pub struct Permalinks {
+    #[config(default = "/:path/:slug/")]
+    pub pattern: String,
+    pub sections: Option<BTreeMap<String, String>>,
+    #[config(default = "trailing-slash")]
+    pub style: UrlStyle,
+    #[config(default = "unicode")]
+    pub slugify: SlugStyle,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UrlStyle {
+    TrailingSlash,
+    NoTrailingSlash,
+    Html,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SlugStyle {
+    Unicode,
+    Transliterate,
+}
+
+#[derive(Clone, Debug, PartialEq, Config)]
+pub struct Markdown {
+    #[config(default = false)]
+    pub heading_anchors: bool,
+    #[config(default = "#")]
+    pub anchor_text: String,
+    #[config(default = true)]
+    pub math: bool,
+    #[config(default = 70)]
+    pub summary_words: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Config)]
+pub struct Highlight {
+    #[config(default = true)]
+    pub enabled: bool,
+    #[config(default = "classes")]
+    pub style: HighlightStyle,
+    #[config(default = "base16-ocean.dark")]
+    pub theme: String,
+    #[config(default = false)]
+    pub line_numbers: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HighlightStyle {
+    Classes,
+    Inline,
+}
+
+#[derive(Clone, Debug, PartialEq, Config)]
+pub struct Archive {
+    #[config(default = true)]
+    pub enabled: bool,
+    #[config(default = "archive")]
+    pub path: String,
+    #[config(default = "%x")]
+    pub date_format: String,
+    #[config(default = "%B %Y")]
+    pub month_format: String,
+    pub paginate_by: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Config)]
+pub struct Series {
+    #[config(default = "series")]
+    pub path: String,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Config)]
+pub struct Related {
+    #[config(default = 5)]
+    pub count: usize,
+    #[config(default = 0.4)]
+    pub taxonomy_weight: f32,
+}
+
+#[derive(Clone, Debug, PartialEq, Config)]
+pub struct Search {
+    #[config(default = true)]
+    pub enabled: bool,
+    #[config(default = "search")]
+    pub path: String,
+    #[config(default = 10000)]
+    pub max_body_chars: usize,
+    #[config(default = 160)]
+    pub max_summary_chars: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Taxonomy {
+    pub name: String,
+    #[serde(default)]
+    pub paginate_by: Option<usize>,
+    #[serde(default = "Taxonomy::default_feed")]
+    pub feed: bool,
+}
+
+impl Taxonomy {
+    pub fn new(name: impl Into<String>) -> Self {
+        Taxonomy {
+            name: name.into(),
+            paginate_by: None,
+            feed: Self::default_feed(),
+        }
+    }
+
+    fn default_feed() -> bool {
+        true
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Config)]
+pub struct Sitemap {
+    #[config(default = true)]
+    pub enabled: bool,
+    #[config(default = 50000)]
+    pub max_urls: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Config)]
+pub struct Robots {
+    #[config(default = true)]
+    pub enabled: bool,
+    #[config(default = ["production"])]
+    pub index_environments: Vec<String>,
+    #[config(default = [])]
+    pub disallow: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Config)]
+pub struct Redirects {
+    #[config(default = 301)]
+    pub status: u16,
+    #[config(default = true)]
+    pub html: bool,
+    #[config(default = true)]
+    pub netlify: bool,
+    #[config(default = false)]
+    pub nginx: bool,
+    pub rules: Option<Vec<RedirectRule>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct RedirectRule {
+    pub from: String,
+    pub to: String,
+    #[serde(default)]
+    pub status: Option<u16>,
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Config)]
 pub struct Static {
     #[config(default = true)]
@@ -119,3 +458,11 @@ pub enum GitUpdate {
         secret: Option<String>,
     },
 }
+
+#[cfg(test)]
+pub fn test_config() -> IlgiConfig {
+    let directory = tempfile::tempdir().unwrap();
+    let file = directory.path().join("ilgi.yml");
+    std::fs::write(&file, "title: Test\nbase_url: https://example.com/\nbuild:\n  git:\n    git_repo: https://example.com/content.git\n").unwrap();
+    IlgiConfig::builder().file(&file).load().unwrap()
+}
@@ -0,0 +1,63 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "articles")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64,
+    pub path: String,
+    pub language: String,
+    pub slug: String,
+    pub title: String,
+    #[sea_orm(column_type = "Text")]
+    pub content: String,
+    #[sea_orm(column_type = "JsonBinary")]
+    pub front_matter: Json,
+    pub commit_sha: Option<String>,
+    pub published_at: Option<DateTimeWithTimeZone>,
+    pub updated_at: Option<DateTimeWithTimeZone>,
+    pub created_at: DateTimeWithTimeZone,
+    pub deleted_at: Option<DateTimeWithTimeZone>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::article_author::Entity")]
+    ArticleAuthor,
+    #[sea_orm(has_many = "super::article_tag::Entity")]
+    ArticleTag,
+}
+
+impl Related<super::article_author::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ArticleAuthor.def()
+    }
+}
+
+impl Related<super::article_tag::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ArticleTag.def()
+    }
+}
+
+impl Related<super::author::Entity> for Entity {
+    fn to() -> RelationDef {
+        super::article_author::Relation::Author.def()
+    }
+
+    fn via() -> Option<RelationDef> {
+        Some(super::article_author::Relation::Article.def().rev())
+    }
+}
+
+impl Related<super::tag::Entity> for Entity {
+    fn to() -> RelationDef {
+        super::article_tag::Relation::Tag.def()
+    }
+
+    fn via() -> Option<RelationDef> {
+        Some(super::article_tag::Relation::Article.def().rev())
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,36 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "authors")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64,
+    #[sea_orm(unique)]
+    pub name: String,
+    pub url: Option<String>,
+    pub avatar: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::article_author::Entity")]
+    ArticleAuthor,
+}
+
+impl Related<super::article_author::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ArticleAuthor.def()
+    }
+}
+
+impl Related<super::article::Entity> for Entity {
+    fn to() -> RelationDef {
+        super::article_author::Relation::Article.def()
+    }
+
+    fn via() -> Option<RelationDef> {
+        Some(super::article_author::Relation::Author.def().rev())
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,49 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum SearchDocuments {
+    Table,
+    Path,
+    Language,
+    Url,
+    Title,
+    Summary,
+    Body,
+    Published,
+    Document,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.create_table(
+            Table::create()
+                .table(SearchDocuments::Table)
+                .col(ColumnDef::new(SearchDocuments::Path).text().not_null())
+                .col(ColumnDef::new(SearchDocuments::Language).text().not_null())
+                .col(ColumnDef::new(SearchDocuments::Url).text().not_null())
+                .col(ColumnDef::new(SearchDocuments::Title).text().not_null())
+                .col(ColumnDef::new(SearchDocuments::Summary).text().not_null())
+                .col(ColumnDef::new(SearchDocuments::Body).text().not_null())
+                .col(ColumnDef::new(SearchDocuments::Published).timestamp_with_time_zone())
+                .col(ColumnDef::new(SearchDocuments::Document).custom(Alias::new("tsvector")).not_null())
+                .primary_key(Index::create().col(SearchDocuments::Path).col(SearchDocuments::Language))
+                .to_owned()
+        ).await?;
+        manager.create_index(
+            Index::create()
+                .name("search_documents_document")
+                .table(SearchDocuments::Table)
+                .col(SearchDocuments::Document)
+                .index_type(IndexType::Custom(Alias::new("GIN").into_iden()))
+                .to_owned()
+        ).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(SearchDocuments::Table).to_owned()).await
+    }
+}
@@ -0,0 +1,153 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum Articles {
+    Table,
+    Id,
+    Path,
+    Language,
+    Slug,
+    Title,
+    Content,
+    FrontMatter,
+    CommitSha,
+    PublishedAt,
+    UpdatedAt,
+    CreatedAt,
+}
+
+#[derive(Iden)]
+enum Authors {
+    Table,
+    Id,
+    Name,
+    Url,
+    Avatar,
+}
+
+#[derive(Iden)]
+enum Tags {
+    Table,
+    Id,
+    Name,
+    Slug,
+}
+
+#[derive(Iden)]
+enum ArticleAuthors {
+    Table,
+    ArticleId,
+    AuthorId,
+    Position,
+}
+
+#[derive(Iden)]
+enum ArticleTags {
+    Table,
+    ArticleId,
+    TagId,
+}
+
+fn id(column: impl IntoIden) -> ColumnDef {
+    ColumnDef::new(column).big_integer().not_null().auto_increment().primary_key().to_owned()
+}
+
+fn reference(name: &str, from: (impl IntoIden, impl IntoIden), to: (impl IntoIden, impl IntoIden)) -> ForeignKeyCreateStatement {
+    ForeignKey::create()
+        .name(name)
+        .from(from.0, from.1)
+        .to(to.0, to.1)
+        .on_delete(ForeignKeyAction::Cascade)
+        .to_owned()
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.create_table(
+            Table::create()
+                .table(Articles::Table)
+                .col(&mut id(Articles::Id))
+                .col(ColumnDef::new(Articles::Path).text().not_null())
+                .col(ColumnDef::new(Articles::Language).text().not_null())
+                .col(ColumnDef::new(Articles::Slug).text().not_null())
+                .col(ColumnDef::new(Articles::Title).text().not_null())
+                .col(ColumnDef::new(Articles::Content).text().not_null())
+                .col(ColumnDef::new(Articles::FrontMatter).json_binary().not_null())
+                .col(ColumnDef::new(Articles::CommitSha).text())
+                .col(ColumnDef::new(Articles::PublishedAt).timestamp_with_time_zone())
+                .col(ColumnDef::new(Articles::UpdatedAt).timestamp_with_time_zone())
+                .col(ColumnDef::new(Articles::CreatedAt).timestamp_with_time_zone().not_null().extra("DEFAULT CURRENT_TIMESTAMP".to_string()))
+                .to_owned()
+        ).await?;
+        manager.create_index(
+            Index::create()
+                .name("articles_path_language")
+                .table(Articles::Table)
+                .col(Articles::Path)
+                .col(Articles::Language)
+                .unique()
+                .to_owned()
+        ).await?;
+        manager.create_index(
+            Index::create()
+                .name("articles_slug_language")
+                .table(Articles::Table)
+                .col(Articles::Slug)
+                .col(Articles::Language)
+                .to_owned()
+        ).await?;
+
+        manager.create_table(
+            Table::create()
+                .table(Authors::Table)
+                .col(&mut id(Authors::Id))
+                .col(ColumnDef::new(Authors::Name).text().not_null().unique_key())
+                .col(ColumnDef::new(Authors::Url).text())
+                .col(ColumnDef::new(Authors::Avatar).text())
+                .to_owned()
+        ).await?;
+
+        manager.create_table(
+            Table::create()
+                .table(Tags::Table)
+                .col(&mut id(Tags::Id))
+                .col(ColumnDef::new(Tags::Name).text().not_null())
+                .col(ColumnDef::new(Tags::Slug).text().not_null().unique_key())
+                .to_owned()
+        ).await?;
+
+        manager.create_table(
+            Table::create()
+                .table(ArticleAuthors::Table)
+                .col(ColumnDef::new(ArticleAuthors::ArticleId).big_integer().not_null())
+                .col(ColumnDef::new(ArticleAuthors::AuthorId).big_integer().not_null())
+                .col(ColumnDef::new(ArticleAuthors::Position).integer().not_null().default(0))
+                .primary_key(Index::create().col(ArticleAuthors::ArticleId).col(ArticleAuthors::AuthorId))
+                .foreign_key(&mut reference("article_authors_article", (ArticleAuthors::Table, ArticleAuthors::ArticleId), (Articles::Table, Articles::Id)))
+                .foreign_key(&mut reference("article_authors_author", (ArticleAuthors::Table, ArticleAuthors::AuthorId), (Authors::Table, Authors::Id)))
+                .to_owned()
+        ).await?;
+
+        manager.create_table(
+            Table::create()
+                .table(ArticleTags::Table)
+                .col(ColumnDef::new(ArticleTags::ArticleId).big_integer().not_null())
+                .col(ColumnDef::new(ArticleTags::TagId).big_integer().not_null())
+                .primary_key(Index::create().col(ArticleTags::ArticleId).col(ArticleTags::TagId))
+                .foreign_key(&mut reference("article_tags_article", (ArticleTags::Table, ArticleTags::ArticleId), (Articles::Table, Articles::Id)))
+                .foreign_key(&mut reference("article_tags_tag", (ArticleTags::Table, ArticleTags::TagId), (Tags::Table, Tags::Id)))
+                .to_owned()
+        ).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for table in [ArticleTags::Table.into_iden(), ArticleAuthors::Table.into_iden(), Tags::Table.into_iden(), Authors::Table.into_iden(), Articles::Table.into_iden()] {
+            manager.drop_table(Table::drop().table(table).to_owned()).await?;
+        }
+        Ok(())
+    }
+}
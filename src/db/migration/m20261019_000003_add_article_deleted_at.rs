@@ -0,0 +1,31 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum Articles {
+    Table,
+    DeletedAt,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.alter_table(
+            Table::alter()
+                .table(Articles::Table)
+                .add_column(ColumnDef::new(Articles::DeletedAt).timestamp_with_time_zone())
+                .to_owned()
+        ).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.alter_table(
+            Table::alter()
+                .table(Articles::Table)
+                .drop_column(Articles::DeletedAt)
+                .to_owned()
+        ).await
+    }
+}
@@ -0,0 +1,18 @@
+use sea_orm_migration::prelude::*;
+
+mod m20261019_000001_create_search_documents;
+mod m20261019_000002_create_articles;
+mod m20261019_000003_add_article_deleted_at;
+
+pub struct Migrator;
+
+#[async_trait::async_trait]
+impl MigratorTrait for Migrator {
+    fn migrations() -> Vec<Box<dyn MigrationTrait>> {
+        vec![
+            Box::new(m20261019_000001_create_search_documents::Migration),
+            Box::new(m20261019_000002_create_articles::Migration),
+            Box::new(m20261019_000003_add_article_deleted_at::Migration),
+        ]
+    }
+}
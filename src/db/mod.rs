@@ -1 +1,54 @@
-mod article;
\ No newline at end of file
+use miette::{IntoDiagnostic, WrapErr};
+use sea_orm::{ConnectOptions, Database, DatabaseConnection};
+use sea_orm_migration::{MigrationStatus, MigratorTrait};
+use ilgi_core::error::{IlgiError, IResult};
+use crate::config::IlgiConfig;
+use crate::db::migration::Migrator;
+
+pub mod article;
+pub mod article_author;
+pub mod article_tag;
+pub mod author;
+pub mod migration;
+pub mod search;
+pub mod sync;
+pub mod tag;
+
+pub async fn connect(config: &IlgiConfig) -> IResult<DatabaseConnection> {
+    let url = config.database.url.as_ref().ok_or(IlgiError::MissingDatabaseUrl)?;
+    let mut options = ConnectOptions::new(url.clone());
+    options.max_connections(config.database.max_connections);
+    Database::connect(options)
+        .await
+        .into_diagnostic()
+        .wrap_err("failed to connect to the database")
+}
+
+pub async fn migrate(db: &DatabaseConnection, steps: Option<u32>) -> IResult<()> {
+    Migrator::up(db, steps)
+        .await
+        .into_diagnostic()
+        .wrap_err("failed to apply migrations")
+}
+
+pub async fn rollback(db: &DatabaseConnection, steps: u32) -> IResult<()> {
+    Migrator::down(db, Some(steps))
+        .await
+        .into_diagnostic()
+        .wrap_err("failed to roll back migrations")
+}
+
+pub async fn migration_status(db: &DatabaseConnection) -> IResult<Vec<(String, bool)>> {
+    let migrations = Migrator::get_migration_with_status(db).await.into_diagnostic()?;
+    Ok(migrations.iter()
+        .map(|migration| (migration.name().to_string(), matches!(migration.status(), MigrationStatus::Applied)))
+        .collect())
+}
+
+pub async fn ensure_migrated(db: &DatabaseConnection) -> IResult<()> {
+    let pending = Migrator::get_pending_migrations(db).await.into_diagnostic()?;
+    match pending.len() {
+        0 => Ok(()),
+        count => Err(IlgiError::PendingMigrations { count }.into()),
+    }
+}
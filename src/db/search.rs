@@ -0,0 +1,256 @@
+use chrono::{DateTime, FixedOffset};
+use itertools::Itertools;
+use miette::{IntoDiagnostic, WrapErr};
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbBackend, Statement, TransactionTrait, Value};
+use serde::Serialize;
+use tracing::instrument;
+use ilgi_core::error::IResult;
+use crate::config::IlgiConfig;
+use crate::formatting::is_cjk;
+use crate::sitebuild::article::Article;
+use crate::sitebuild::markdown::escape_text;
+use crate::sitebuild::search::{body_text, heading_titles, tokenize, truncate_chars};
+
+const INSERT_DOCUMENT: &str = "INSERT INTO search_documents (path, language, url, title, summary, body, published, document)
+    VALUES ($1, $2, $3, $4, $5, $6, $7,
+        setweight(to_tsvector($8::text::regconfig, $4), 'A')
+        || setweight(to_tsvector($8::text::regconfig, $9), 'B')
+        || setweight(to_tsvector('simple', $10), 'C')
+        || setweight(to_tsvector($8::text::regconfig, $6), 'D'))";
+
+const SEARCH_DOCUMENTS: &str = "SELECT url, title, summary, published,
+        ts_rank_cd(document, query.terms) AS rank,
+        ts_headline($1::text::regconfig, body, query.terms, 'StartSel=<mark>, StopSel=</mark>, MaxFragments=2, MaxWords=30, MinWords=10') AS snippet,
+        count(*) OVER () AS total
+    FROM search_documents,
+        (SELECT websearch_to_tsquery($1::text::regconfig, $2) && plainto_tsquery('simple', $3) AS terms) AS query
+    WHERE language = $4 AND document @@ query.terms
+    ORDER BY rank DESC, published DESC NULLS LAST, path
+    LIMIT $5 OFFSET $6";
+
+const TEXT_SEARCH_CONFIGS: &[(&str, &str)] = &[
+    ("da", "danish"), ("de", "german"), ("en", "english"), ("es", "spanish"), ("fi", "finnish"), ("fr", "french"),
+    ("hu", "hungarian"), ("it", "italian"), ("nb", "norwegian"), ("nl", "dutch"), ("no", "norwegian"), ("pt", "portuguese"),
+    ("ro", "romanian"), ("ru", "russian"), ("sv", "swedish"), ("tr", "turkish"),
+];
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SearchResult {
+    pub url: String,
+    pub title: String,
+    pub summary: String,
+    pub snippet: String,
+    pub published: Option<DateTime<FixedOffset>>,
+    pub rank: f32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SearchResults {
+    pub query: String,
+    pub language: String,
+    pub page: u64,
+    pub per_page: u64,
+    pub total: u64,
+    pub results: Vec<SearchResult>,
+}
+
+pub fn text_search_config(config: &IlgiConfig, language: &str) -> String {
+    let base = language.split(['-', '_']).next().unwrap_or(language);
+    config.search_api.languages.iter()
+        .flatten()
+        .find(|(code, _)| code.as_str() == language)
+        .map(|(_, name)| name.as_str())
+        .or_else(|| TEXT_SEARCH_CONFIGS.iter().find(|(code, _)| *code == base).map(|(_, name)| *name))
+        .unwrap_or("simple")
+        .to_string()
+}
+
+fn split_cjk(text: &str) -> (String, String) {
+    let bigrams = tokenize(text).into_iter()
+        .filter(|token| token.chars().any(is_cjk))
+        .join(" ");
+    let rest = text.chars()
+        .map(|character| if is_cjk(character) { ' ' } else { character })
+        .collect::<String>();
+    (rest, bigrams)
+}
+
+fn statement(sql: &str, values: impl IntoIterator<Item = Value>) -> Statement {
+    Statement::from_sql_and_values(DbBackend::Postgres, sql, values)
+}
+
+#[instrument(skip_all)]
+pub async fn index_articles(db: &DatabaseConnection, config: &IlgiConfig, articles: &[Article]) -> IResult<()> {
+    let transaction = db.begin().await.into_diagnostic()?;
+    transaction.execute(statement("DELETE FROM search_documents", [])).await.into_diagnostic()?;
+    for article in articles.iter().filter(|article| article.published && article.searchable) {
+        let body = body_text(&article.content);
+        let body = truncate_chars(&body, config.build.search.max_body_chars);
+        let labels = heading_titles(&article.toc).into_iter()
+            .chain(article.taxonomies.values().flatten().cloned())
+            .join(" ");
+        let (_, bigrams) = split_cjk(&format!("{} {labels} {body}", article.title));
+        let summary = article.description.clone().unwrap_or_else(|| article.summary_text.clone());
+
+        transaction.execute(statement(INSERT_DOCUMENT, [
+            article.source.clone().into(),
+            article.language.clone().into(),
+            article.permalink.clone().into(),
+            article.title.clone().into(),
+            summary.into(),
+            escape_text(body).into(),
+            article.date.into(),
+            text_search_config(config, &article.language).into(),
+            labels.into(),
+            bigrams.into(),
+        ]))
+        .await
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to index {} for search", article.source))?;
+    }
+    transaction.commit().await.into_diagnostic()
+}
+
+#[instrument(skip(db, config))]
+pub async fn search(db: &DatabaseConnection, config: &IlgiConfig, language: &str, query: &str, page: u64, per_page: u64) -> IResult<SearchResults> {
+    let mut results = SearchResults {
+        query: query.to_string(),
+        language: language.to_string(),
+        page,
+        per_page,
+        total: 0,
+        results: vec![],
+    };
+    let (words, bigrams) = split_cjk(query);
+    if words.trim().is_empty() && bigrams.is_empty() {
+        return Ok(results);
+    }
+
+    let rows = db.query_all(statement(SEARCH_DOCUMENTS, [
+        text_search_config(config, language).into(),
+        words.into(),
+        bigrams.into(),
+        language.into(),
+        (per_page as i64).into(),
+        ((page.saturating_sub(1) * per_page) as i64).into(),
+    ]))
+    .await
+    .into_diagnostic()
+    .wrap_err("failed to search the database")?;
+
+    for row in rows {
+        results.total = row.try_get::<i64>("", "total").into_diagnostic()? as u64;
+        results.results.push(SearchResult {
+            url: row.try_get("", "url").into_diagnostic()?,
+            title: row.try_get("", "title").into_diagnostic()?,
+            summary: row.try_get("", "summary").into_diagnostic()?,
+            snippet: row.try_get("", "snippet").into_diagnostic()?,
+            published: row.try_get("", "published").into_diagnostic()?,
+            rank: row.try_get("", "rank").into_diagnostic()?,
+        });
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{ConnectOptions, Database};
+    use crate::config::test_config;
+    use crate::db;
+    use super::*;
+
+    // Each test works in its own schema of the database named by ILGI_TEST_DATABASE_URL, so it never
+    // touches the tables of the configured site database.
+    async fn database(schema: &str) -> (DatabaseConnection, IlgiConfig) {
+        let url = std::env::var("ILGI_TEST_DATABASE_URL").expect("ILGI_TEST_DATABASE_URL names a Postgres database for tests");
+        let admin = Database::connect(&url).await.unwrap();
+        admin.execute_unprepared(&format!("DROP SCHEMA IF EXISTS {schema} CASCADE")).await.unwrap();
+        admin.execute_unprepared(&format!("CREATE SCHEMA {schema}")).await.unwrap();
+
+        let mut options = ConnectOptions::new(url);
+        options.set_schema_search_path(schema.to_string());
+        let database = Database::connect(options).await.unwrap();
+        db::migrate(&database, None).await.unwrap();
+        (database, test_config())
+    }
+
+    fn article(source: &str, language: &str, title: &str, content: &str) -> Article {
+        Article {
+            source: source.to_string(),
+            language: language.to_string(),
+            permalink: format!("https://example.com/{}/", source.trim_end_matches(".md")),
+            title: title.to_string(),
+            content: content.to_string(),
+            published: true,
+            searchable: true,
+            ..Default::default()
+        }
+    }
+
+    async fn titles(database: &DatabaseConnection, config: &IlgiConfig, language: &str, query: &str) -> Vec<String> {
+        let results = search(database, config, language, query, 1, 10).await.unwrap();
+        results.results.into_iter().map(|result| result.title).collect()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres, set ILGI_TEST_DATABASE_URL and run with --ignored"]
+    async fn ranks_title_matches_first() {
+        let (database, config) = database("search_ranks_title_matches_first").await;
+        let mut draft = article("draft.md", "en", "Rust drafts", "<p>Unfinished rust notes.</p>");
+        draft.published = false;
+        index_articles(&database, &config, &[
+            article("notes.md", "en", "Notes", "<p>Some notes about rust and other things.</p>"),
+            article("rust.md", "en", "Rust", "<p>A language.</p>"),
+            article("other.md", "en", "Other", "<p>Nothing to see.</p>"),
+            draft,
+        ]).await.unwrap();
+
+        let results = search(&database, &config, "en", "rust", 1, 10).await.unwrap();
+        assert_eq!(results.total, 2);
+        assert_eq!(results.results.iter().map(|result| result.title.as_str()).collect::<Vec<_>>(), ["Rust", "Notes"]);
+        assert!(results.results[0].rank > results.results[1].rank);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres, set ILGI_TEST_DATABASE_URL and run with --ignored"]
+    async fn finds_cjk_bigrams() {
+        let (database, config) = database("search_finds_cjk_bigrams").await;
+        index_articles(&database, &config, &[
+            article("search.md", "ko", "검색 엔진", "<p>한국어 형태소 분석 없이 찾기</p>"),
+            article("other.md", "ko", "Other", "<p>English only</p>"),
+        ]).await.unwrap();
+
+        assert_eq!(titles(&database, &config, "ko", "국어").await, ["검색 엔진"]);
+        assert_eq!(titles(&database, &config, "ko", "형태소 분석").await, ["검색 엔진"]);
+        assert!(titles(&database, &config, "ko", "국문").await.is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres, set ILGI_TEST_DATABASE_URL and run with --ignored"]
+    async fn stems_with_the_language_configuration() {
+        let (database, config) = database("search_stems_with_the_language_configuration").await;
+        index_articles(&database, &config, &[
+            article("running.md", "en", "Running", "<p>She was running quickly.</p>"),
+            article("running.md", "xx", "Running", "<p>She was running quickly.</p>"),
+        ]).await.unwrap();
+
+        assert_eq!(text_search_config(&config, "en"), "english");
+        assert_eq!(text_search_config(&config, "xx"), "simple");
+        assert_eq!(titles(&database, &config, "en", "runs").await, ["Running"]);
+        assert!(titles(&database, &config, "xx", "runs").await.is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres, set ILGI_TEST_DATABASE_URL and run with --ignored"]
+    async fn escapes_snippets() {
+        let (database, config) = database("search_escapes_snippets").await;
+        index_articles(&database, &config, &[
+            article("markup.md", "en", "Markup", "<p>Write &lt;script&gt;alert(1)&lt;/script&gt; before the closing tag.</p>"),
+        ]).await.unwrap();
+
+        let results = search(&database, &config, "en", "alert", 1, 10).await.unwrap();
+        let snippet = &results.results[0].snippet;
+        assert!(snippet.contains("&lt;script&gt;<mark>alert</mark>(1)&lt;/script&gt;"), "{snippet}");
+        assert!(!snippet.contains("<script>"), "{snippet}");
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::BTreeMap;
+use miette::{IntoDiagnostic, WrapErr};
+use sea_orm::{ConnectionTrait, DatabaseConnection, DatabaseTransaction, DbBackend, DbErr, Statement, TransactionTrait, Value};
+use tracing::{info, instrument};
+use ilgi_core::error::IResult;
+use crate::config::IlgiConfig;
+use crate::sitebuild::article::Article;
+use crate::sitebuild::slug::url_slug;
+use crate::sitebuild::Site;
+
+const UPSERT_ARTICLE: &str = "INSERT INTO articles (path, language, slug, title, content, front_matter, commit_sha, published_at, updated_at)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+    ON CONFLICT (path, language) DO UPDATE SET
+        slug = EXCLUDED.slug,
+        title = EXCLUDED.title,
+        content = EXCLUDED.content,
+        front_matter = EXCLUDED.front_matter,
+        commit_sha = EXCLUDED.commit_sha,
+        published_at = EXCLUDED.published_at,
+        updated_at = EXCLUDED.updated_at,
+        deleted_at = NULL
+    RETURNING id";
+
+const UPSERT_AUTHOR: &str = "INSERT INTO authors (name, url, avatar) VALUES ($1, $2, $3)
+    ON CONFLICT (name) DO UPDATE SET
+        url = COALESCE(EXCLUDED.url, authors.url),
+        avatar = COALESCE(EXCLUDED.avatar, authors.avatar)
+    RETURNING id";
+
+const UPSERT_TAG: &str = "INSERT INTO tags (name, slug) VALUES ($1, $2)
+    ON CONFLICT (slug) DO UPDATE SET name = tags.name
+    RETURNING id";
+
+const SOFT_DELETE_ARTICLES: &str = "UPDATE articles SET deleted_at = now() WHERE deleted_at IS NULL AND id <> ALL($1)";
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SyncSummary {
+    pub upserted: usize,
+    pub deleted: u64,
+}
+
+fn statement(sql: &str, values: impl IntoIterator<Item = Value>) -> Statement {
+    Statement::from_sql_and_values(DbBackend::Postgres, sql, values)
+}
+
+fn toml_to_json(value: toml::Value) -> serde_json::Value {
+    match value {
+        toml::Value::String(string) => string.into(),
+        toml::Value::Integer(integer) => integer.into(),
+        toml::Value::Float(float) => float.into(),
+        toml::Value::Boolean(boolean) => boolean.into(),
+        toml::Value::Datetime(datetime) => datetime.to_string().into(),
+        toml::Value::Array(array) => array.into_iter().map(toml_to_json).collect(),
+        toml::Value::Table(table) => table.into_iter().map(|(key, value)| (key, toml_to_json(value))).collect(),
+    }
+}
+
+fn is_synced(article: &Article) -> bool {
+    !article.draft && (article.published || article.expired)
+}
+
+async fn returning_id(transaction: &DatabaseTransaction, statement: Statement) -> IResult<i64> {
+    let row = transaction.query_one(statement)
+        .await
+        .and_then(|row| row.ok_or_else(|| DbErr::RecordNotFound("no id was returned".to_string())))
+        .into_diagnostic()?;
+    row.try_get("", "id").into_diagnostic()
+}
+
+async fn sync_article(transaction: &DatabaseTransaction, config: &IlgiConfig, commit: Option<&str>, article: &Article) -> IResult<i64> {
+    let id = returning_id(transaction, statement(UPSERT_ARTICLE, [
+        article.source.clone().into(),
+        article.language.clone().into(),
+        article.slug.clone().into(),
+        article.title.clone().into(),
+        article.content.clone().into(),
+        toml_to_json(toml::Value::Table(article.front_matter.clone())).into(),
+        commit.map(str::to_string).into(),
+        article.date.into(),
+        article.updated.into(),
+    ])).await?;
+
+    transaction.execute(statement("DELETE FROM article_authors WHERE article_id = $1", [id.into()])).await.into_diagnostic()?;
+    for (position, author) in article.authors.iter().enumerate() {
+        let author_id = returning_id(transaction, statement(UPSERT_AUTHOR, [
+            author.name.clone().into(),
+            author.url.clone().into(),
+            author.avatar.clone().into(),
+        ])).await?;
+        transaction.execute(statement(
+            "INSERT INTO article_authors (article_id, author_id, position) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+            [id.into(), author_id.into(), (position as i32).into()],
+        )).await.into_diagnostic()?;
+    }
+
+    transaction.execute(statement("DELETE FROM article_tags WHERE article_id = $1", [id.into()])).await.into_diagnostic()?;
+    let tags = article.terms("tags").iter()
+        .map(|tag| (url_slug(&config.build.permalinks, tag), tag.trim()))
+        .filter(|(slug, _)| !slug.is_empty())
+        .collect::<BTreeMap<_, _>>();
+    for (slug, name) in tags {
+        let tag_id = returning_id(transaction, statement(UPSERT_TAG, [name.into(), slug.into()])).await?;
+        transaction.execute(statement(
+            "INSERT INTO article_tags (article_id, tag_id) VALUES ($1, $2)",
+            [id.into(), tag_id.into()],
+        )).await.into_diagnostic()?;
+    }
+    Ok(id)
+}
+
+#[instrument(skip_all)]
+pub async fn sync_site(db: &DatabaseConnection, config: &IlgiConfig, site: &Site) -> IResult<SyncSummary> {
+    let transaction = db.begin().await.into_diagnostic()?;
+    let mut ids = vec![];
+    for article in site.articles.iter().chain(&site.unlisted).filter(|article| is_synced(article)) {
+        let id = sync_article(&transaction, config, site.commit.as_deref(), article)
+            .await
+            .wrap_err_with(|| format!("failed to store {} in the database", article.source))?;
+        ids.push(id);
+    }
+    let deleted = transaction.execute(statement(SOFT_DELETE_ARTICLES, [ids.clone().into()]))
+        .await
+        .into_diagnostic()
+        .wrap_err("failed to mark removed articles as deleted")?
+        .rows_affected();
+    transaction.commit().await.into_diagnostic()?;
+
+    let summary = SyncSummary { upserted: ids.len(), deleted };
+    info!("stored {} articles in the database, {} removed", summary.upserted, summary.deleted);
+    Ok(summary)
+}
@@ -1,10 +1,14 @@
+use std::path::{Path, PathBuf};
 use std::str::from_utf8;
+use ignore::WalkBuilder;
 use lightningcss::printer::PrinterOptions;
 use lightningcss::stylesheet::{MinifyOptions, ParserOptions, StyleSheet};
 use lightningcss::targets::Browsers;
 use memmap2::Mmap;
+use miette::IntoDiagnostic;
 use minify_js::{Session, TopLevelMode};
 use oxipng::Options;
+use relative_path::RelativePath;
 use rimage::{ImageData, OutputFormat};
 use ilgi_core::error::IResult;
 use crate::config::IlgiConfig;
@@ -118,3 +122,26 @@ pub fn decode_hash_filename(filename: impl AsRef<str>) -> Option<(String, u64)>
         None => None,
     }
 }
+
+pub fn load_dir(path: impl AsRef<Path>) -> IResult<impl Iterator<Item = PathBuf>> {
+    Ok(
+        WalkBuilder::new(path.as_ref()).add_custom_ignore_filename(".ilgi_ignore")
+            .build()
+            .map(|file| {
+                file.map(|x| {
+                    x.into_path()
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .into_diagnostic()?
+            .into_iter()
+    )
+}
+
+pub async fn write_output(output: impl AsRef<Path>, path: impl AsRef<str>, data: impl AsRef<[u8]>) -> IResult<()> {
+    let target = RelativePath::new(path.as_ref()).to_logical_path(output.as_ref());
+    if let Some(parent) = target.parent() {
+        tokio::fs::create_dir_all(parent).await.into_diagnostic()?;
+    }
+    tokio::fs::write(target, data).await.into_diagnostic()
+}
@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+use pure_rust_locales::{locale_match, Locale};
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use upon::{Engine as UponEngine, Value as UponValue};
+use crate::i18n::page_language;
+use crate::sitebuild::locale::{locale_for, locale_name};
+use crate::sitebuild::summary::{decode_entities, visible_tokens, HtmlToken};
+
+pub const DEFAULT_DATE_FORMAT: &str = "%x";
+pub const WORDS_PER_MINUTE: usize = 200;
+pub const CJK_CHARACTERS_PER_MINUTE: usize = 500;
+pub const CODE_LINES_PER_MINUTE: usize = 60;
+
+struct Phrases {
+    past: &'static str,
+    future: &'static str,
+    separator: &'static str,
+    units: [(&'static str, &'static str); 7],
+    reading_time: &'static str,
+}
+
+const ENGLISH: Phrases = Phrases {
+    past: "{} ago",
+    future: "in {}",
+    separator: " ",
+    units: [("second", "seconds"), ("minute", "minutes"), ("hour", "hours"), ("day", "days"), ("week", "weeks"), ("month", "months"), ("year", "years")],
+    reading_time: "{} min read",
+};
+
+const PHRASES: &[(&str, Phrases)] = &[
+    ("en", ENGLISH),
+    ("de", Phrases {
+        past: "vor {}",
+        future: "in {}",
+        separator: " ",
+        units: [("Sekunde", "Sekunden"), ("Minute", "Minuten"), ("Stunde", "Stunden"), ("Tag", "Tagen"), ("Woche", "Wochen"), ("Monat", "Monaten"), ("Jahr", "Jahren")],
+        reading_time: "{} Min. Lesezeit",
+    }),
+    ("es", Phrases {
+        past: "hace {}",
+        future: "dentro de {}",
+        separator: " ",
+        units: [("segundo", "segundos"), ("minuto", "minutos"), ("hora", "horas"), ("día", "días"), ("semana", "semanas"), ("mes", "meses"), ("año", "años")],
+        reading_time: "{} min de lectura",
+    }),
+    ("fr", Phrases {
+        past: "il y a {}",
+        future: "dans {}",
+        separator: " ",
+        units: [("seconde", "secondes"), ("minute", "minutes"), ("heure", "heures"), ("jour", "jours"), ("semaine", "semaines"), ("mois", "mois"), ("an", "ans")],
+        reading_time: "{} min de lecture",
+    }),
+    ("ja", Phrases {
+        past: "{}前",
+        future: "{}後",
+        separator: "",
+        units: [("秒", "秒"), ("分", "分"), ("時間", "時間"), ("日", "日"), ("週間", "週間"), ("か月", "か月"), ("年", "年")],
+        reading_time: "{}分で読めます",
+    }),
+    ("ko", Phrases {
+        past: "{} 전",
+        future: "{} 후",
+        separator: "",
+        units: [("초", "초"), ("분", "분"), ("시간", "시간"), ("일", "일"), ("주", "주"), ("개월", "개월"), ("년", "년")],
+        reading_time: "{}분 분량",
+    }),
+    ("zh", Phrases {
+        past: "{}前",
+        future: "{}后",
+        separator: " ",
+        units: [("秒", "秒"), ("分钟", "分钟"), ("小时", "小时"), ("天", "天"), ("周", "周"), ("个月", "个月"), ("年", "年")],
+        reading_time: "阅读需 {} 分钟",
+    }),
+];
+
+fn phrases(language: &str) -> &'static Phrases {
+    let primary = language.split(['-', '_']).next().unwrap_or(language).to_lowercase();
+    PHRASES.iter()
+        .find(|(code, _)| *code == primary)
+        .map(|(_, phrases)| phrases)
+        .unwrap_or(&ENGLISH)
+}
+
+pub fn parse_datetime(value: &str, timezone: &Tz) -> Option<DateTime<FixedOffset>> {
+    let value = value.trim();
+    let local = |naive: NaiveDateTime| timezone.from_local_datetime(&naive)
+        .earliest()
+        .map(|datetime| datetime.with_timezone(&datetime.offset().fix()));
+
+    DateTime::parse_from_rfc3339(value).ok()
+        .or_else(|| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S").ok().and_then(local))
+        .or_else(|| NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").ok().and_then(local))
+        .or_else(|| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok().and_then(|date| local(date.and_hms_opt(0, 0, 0)?)))
+        .or_else(|| value.parse::<i64>().ok().and_then(|timestamp| Utc.timestamp_opt(timestamp, 0).single()).map(|datetime| datetime.with_timezone(&Utc.fix())))
+}
+
+pub fn format_date(datetime: &DateTime<FixedOffset>, timezone: &Tz, format: &str, language: &str) -> String {
+    datetime.with_timezone(timezone)
+        .format_localized(format, locale_for(language))
+        .to_string()
+}
+
+fn group_digits(digits: &str, separator: &str, grouping: &[i64]) -> String {
+    let mut groups = vec![];
+    let mut rest = digits;
+    let mut sizes = grouping.iter().copied();
+    let mut size = sizes.next().unwrap_or(-1);
+    while size > 0 && rest.len() > size as usize {
+        let (head, tail) = rest.split_at(rest.len() - size as usize);
+        groups.push(tail);
+        rest = head;
+        size = sizes.next().unwrap_or(size);
+    }
+    groups.push(rest);
+    groups.reverse();
+    groups.join(separator)
+}
+
+pub fn format_number(value: f64, language: &str, decimals: Option<usize>) -> String {
+    let locale = Locale::try_from(locale_name(language).as_str()).unwrap_or(Locale::POSIX);
+    let decimal_point = locale_match!(locale => LC_NUMERIC::DECIMAL_POINT);
+    let separator = locale_match!(locale => LC_NUMERIC::THOUSANDS_SEP);
+    let grouping = locale_match!(locale => LC_NUMERIC::GROUPING);
+
+    let formatted = match decimals {
+        Some(decimals) => format!("{:.*}", decimals, value.abs()),
+        None => value.abs().to_string(),
+    };
+    let (integer, fraction) = formatted.split_once('.').unwrap_or((&formatted, ""));
+    let mut number = String::new();
+    if value.is_sign_negative() && value != 0.0 {
+        number.push('-');
+    }
+    number.push_str(&group_digits(integer, separator, grouping));
+    if !fraction.is_empty() {
+        number.push_str(decimal_point);
+        number.push_str(fraction);
+    }
+    number
+}
+
+pub fn relative_time(datetime: &DateTime<FixedOffset>, now: &DateTime<Utc>, language: &str) -> String {
+    let phrases = phrases(language);
+    let seconds = now.signed_duration_since(*datetime).num_seconds();
+    let elapsed = seconds.unsigned_abs();
+    let (unit, count) = match elapsed {
+        0..=59 => (0, elapsed),
+        60..=3_599 => (1, elapsed / 60),
+        3_600..=86_399 => (2, elapsed / 3_600),
+        86_400..=604_799 => (3, elapsed / 86_400),
+        604_800..=2_591_999 => (4, elapsed / 604_800),
+        2_592_000..=31_535_999 => (5, elapsed / 2_592_000),
+        _ => (6, elapsed / 31_536_000),
+    };
+
+    let (singular, plural) = phrases.units[unit];
+    let amount = format!("{}{}{}", format_number(count as f64, language, None), phrases.separator, if count == 1 { singular } else { plural });
+    let template = if seconds >= 0 { phrases.past } else { phrases.future };
+    template.replace("{}", &amount)
+}
+
+pub fn is_cjk(character: char) -> bool {
+    matches!(character,
+        '\u{1100}'..='\u{11FF}' | '\u{2E80}'..='\u{2FDF}' | '\u{3040}'..='\u{30FF}' | '\u{3130}'..='\u{318F}' |
+        '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' | '\u{AC00}'..='\u{D7AF}' | '\u{F900}'..='\u{FAFF}' |
+        '\u{20000}'..='\u{2FA1F}'
+    )
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct TextStats {
+    pub words: usize,
+    pub cjk_characters: usize,
+    pub code_lines: usize,
+}
+
+impl TextStats {
+    pub fn of_html(html: &str) -> TextStats {
+        let mut stats = TextStats::default();
+        let mut code = String::new();
+        let mut preformatted = 0usize;
+        for token in visible_tokens(html) {
+            match token {
+                HtmlToken::Open { name, void: false, .. } if name == "pre" => preformatted += 1,
+                HtmlToken::Close { name, .. } if name == "pre" => {
+                    preformatted = preformatted.saturating_sub(1);
+                    if preformatted == 0 {
+                        stats.code_lines += code.lines().filter(|line| !line.trim().is_empty()).count();
+                        code.clear();
+                    }
+                }
+                HtmlToken::Text(text) if preformatted > 0 => code.push_str(text),
+                HtmlToken::Text(text) => stats.add_text(&decode_entities(text)),
+                _ => {}
+            }
+        }
+        stats.code_lines += code.lines().filter(|line| !line.trim().is_empty()).count();
+        stats
+    }
+
+    fn add_text(&mut self, text: &str) {
+        self.cjk_characters += text.chars().filter(|character| is_cjk(*character)).count();
+        self.words += text.split(|character: char| character.is_whitespace() || is_cjk(character))
+            .filter(|word| word.chars().any(char::is_alphanumeric))
+            .count();
+    }
+
+    pub fn word_count(&self) -> usize {
+        self.words + self.cjk_characters
+    }
+
+    pub fn reading_minutes(&self) -> usize {
+        let minutes = self.words as f64 / WORDS_PER_MINUTE as f64
+            + self.cjk_characters as f64 / CJK_CHARACTERS_PER_MINUTE as f64
+            + self.code_lines as f64 / CODE_LINES_PER_MINUTE as f64;
+        (minutes.ceil() as usize).max(1)
+    }
+}
+
+pub fn reading_minutes(text: &str) -> usize {
+    TextStats::of_html(text).reading_minutes()
+}
+
+pub fn reading_time(minutes: usize, language: &str) -> String {
+    phrases(language).reading_time.replace("{}", &format_number(minutes as f64, language, None))
+}
+
+fn tera_locale(args: &HashMap<String, JsonValue>, default_language: &str) -> String {
+    tera_string(args, "locale")
+        .map(str::to_string)
+        .or_else(page_language)
+        .unwrap_or_else(|| default_language.to_string())
+}
+
+fn tera_string<'a>(args: &'a HashMap<String, JsonValue>, name: &str) -> Option<&'a str> {
+    args.get(name).and_then(JsonValue::as_str)
+}
+
+fn tera_datetime(filter: &str, value: &JsonValue, timezone: &Tz) -> tera::Result<DateTime<FixedOffset>> {
+    let parsed = match value {
+        JsonValue::String(value) => parse_datetime(value, timezone),
+        JsonValue::Number(value) => value.as_i64().and_then(|timestamp| parse_datetime(&timestamp.to_string(), timezone)),
+        _ => None,
+    };
+    parsed.ok_or_else(|| tera::Error::msg(format!("{filter} cannot read {value} as a date")))
+}
+
+fn tera_timezone(args: &HashMap<String, JsonValue>, timezone: Tz) -> tera::Result<Tz> {
+    match tera_string(args, "timezone") {
+        Some(name) => name.parse::<Tz>().map_err(|_| tera::Error::msg(format!("unknown time zone {name}"))),
+        None => Ok(timezone),
+    }
+}
+
+pub fn register_tera(tera: &mut tera::Tera, timezone: Tz, default_language: &str) {
+    let language = default_language.to_string();
+    tera.register_filter("local_date", move |value: &JsonValue, args: &HashMap<String, JsonValue>| {
+        let timezone = tera_timezone(args, timezone)?;
+        let datetime = tera_datetime("local_date", value, &timezone)?;
+        let format = tera_string(args, "format").unwrap_or(DEFAULT_DATE_FORMAT);
+        Ok(JsonValue::String(format_date(&datetime, &timezone, format, &tera_locale(args, &language))))
+    });
+
+    let language = default_language.to_string();
+    tera.register_filter("number", move |value: &JsonValue, args: &HashMap<String, JsonValue>| {
+        let number = value.as_f64()
+            .or_else(|| value.as_str().and_then(|value| value.trim().parse().ok()))
+            .ok_or_else(|| tera::Error::msg(format!("number cannot read {value} as a number")))?;
+        let decimals = args.get("decimals").and_then(JsonValue::as_u64).map(|decimals| decimals as usize);
+        Ok(JsonValue::String(format_number(number, &tera_locale(args, &language), decimals)))
+    });
+
+    let language = default_language.to_string();
+    // Pages are static, so relative times are measured from the build and only move on the next rebuild.
+    tera.register_filter("relative_time", move |value: &JsonValue, args: &HashMap<String, JsonValue>| {
+        let datetime = tera_datetime("relative_time", value, &timezone)?;
+        Ok(JsonValue::String(relative_time(&datetime, &Utc::now(), &tera_locale(args, &language))))
+    });
+
+    let language = default_language.to_string();
+    tera.register_filter("reading_time", move |value: &JsonValue, args: &HashMap<String, JsonValue>| {
+        let minutes = match value {
+            JsonValue::String(text) => reading_minutes(text),
+            JsonValue::Number(minutes) => minutes.as_u64().unwrap_or_default() as usize,
+            other => return Err(tera::Error::msg(format!("reading_time expects text or minutes, not {other}"))),
+        };
+        Ok(JsonValue::String(reading_time(minutes, &tera_locale(args, &language))))
+    });
+}
+
+pub fn register_upon(upon: &mut UponEngine<'static>, timezone: Tz) {
+    upon.add_filter("date", move |value: &str, format: &str, locale: &str| -> Result<String, String> {
+        parse_datetime(value, &timezone)
+            .map(|datetime| format_date(&datetime, &timezone, format, locale))
+            .ok_or_else(|| format!("date cannot read {value} as a date"))
+    });
+    upon.add_filter("number", |value: &UponValue, locale: &str| -> Result<String, String> {
+        match value {
+            UponValue::Integer(integer) => Ok(format_number(*integer as f64, locale, None)),
+            UponValue::Float(float) => Ok(format_number(*float, locale, None)),
+            _ => Err("number expects an integer or a float".to_string()),
+        }
+    });
+    upon.add_filter("relative_time", move |value: &str, locale: &str| -> Result<String, String> {
+        parse_datetime(value, &timezone)
+            .map(|datetime| relative_time(&datetime, &Utc::now(), locale))
+            .ok_or_else(|| format!("relative_time cannot read {value} as a date"))
+    });
+    upon.add_filter("reading_time", |value: &UponValue, locale: &str| -> Result<String, String> {
+        match value {
+            UponValue::String(text) => Ok(reading_time(reading_minutes(text), locale)),
+            UponValue::Integer(minutes) => Ok(reading_time((*minutes).max(0) as usize, locale)),
+            _ => Err("reading_time expects text or minutes".to_string()),
+        }
+    });
+    upon.add_formatter("safe", |formatter, value| match value {
+        UponValue::String(html) => Ok(formatter.write_str(html)?),
+        value => upon::fmt::default(formatter, value),
+    });
+}
@@ -0,0 +1,224 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+use dashmap::DashSet;
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource, FluentValue};
+use fluent_syntax::ast::Entry;
+use itertools::Itertools;
+use rhai::{Dynamic, Engine, Map};
+use tracing::warn;
+use unic_langid::LanguageIdentifier;
+use upon::{Engine as UponEngine, Value as UponValue};
+use ilgi_core::error::{IlgiError, IResult};
+
+thread_local! {
+    static PAGE_LANGUAGE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+// Template functions translate into the language of the page being rendered unless they are given one.
+pub fn with_page_language<T>(language: Option<&str>, render: impl FnOnce() -> T) -> T {
+    let previous = PAGE_LANGUAGE.with(|current| current.replace(language.map(str::to_string)));
+    let result = render();
+    PAGE_LANGUAGE.with(|current| *current.borrow_mut() = previous);
+    result
+}
+
+pub fn page_language() -> Option<String> {
+    PAGE_LANGUAGE.with(|current| current.borrow().clone())
+}
+
+pub struct Translations {
+    pub default_language: String,
+    bundles: HashMap<String, FluentBundle<FluentResource>>,
+    reported: DashSet<(String, String)>,
+}
+
+impl Debug for Translations {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Translations")
+            .field("default_language", &self.default_language)
+            .field("languages", &self.bundles.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Translations {
+    pub fn load(default_language: &str, catalogs: impl IntoIterator<Item = (String, String)>) -> IResult<Translations> {
+        let mut bundles = HashMap::new();
+        let mut keys = HashMap::<String, HashSet<String>>::new();
+
+        for (name, source) in catalogs {
+            let Some(language) = name.strip_suffix(".ftl") else {
+                warn!("ignoring theme translation catalog {name}, only Fluent (.ftl) catalogs are supported");
+                continue
+            };
+            let invalid = |message: String| IlgiError::InvalidTranslationCatalog { path: name.clone(), message };
+
+            let identifier = language.parse::<LanguageIdentifier>()
+                .map_err(|why| invalid(why.to_string()))?;
+            let resource = FluentResource::try_new(source)
+                .map_err(|(_, errors)| invalid(errors.iter().map(ToString::to_string).join(", ")))?;
+            keys.insert(
+                language.to_string(),
+                resource.entries()
+                    .filter_map(|entry| match entry {
+                        Entry::Message(message) => Some(message.id.name.to_string()),
+                        _ => None,
+                    })
+                    .collect(),
+            );
+
+            let mut bundle = FluentBundle::new_concurrent(vec![identifier]);
+            bundle.set_use_isolating(false);
+            bundle.add_resource(resource)
+                .map_err(|errors| invalid(errors.iter().map(ToString::to_string).join(", ")))?;
+            bundles.insert(language.to_string(), bundle);
+        }
+
+        match keys.get(default_language) {
+            Some(default_keys) => {
+                for (language, language_keys) in keys.iter().filter(|(language, _)| *language != default_language) {
+                    for key in default_keys.difference(language_keys).sorted() {
+                        warn!("theme translation \"{key}\" is missing from {language}.ftl and will fall back to {default_language}");
+                    }
+                }
+            }
+            None if !bundles.is_empty() => {
+                warn!("the theme has no translation catalog for its default language {default_language}");
+            }
+            None => {}
+        }
+
+        Ok(
+            Translations {
+                default_language: default_language.to_string(),
+                bundles,
+                reported: DashSet::new(),
+            }
+        )
+    }
+
+    pub fn translate(&self, language: &str, key: &str, args: Option<&FluentArgs>) -> String {
+        let primary = language.split(['-', '_']).next().unwrap_or(language);
+        for candidate in [language, primary, self.default_language.as_str()] {
+            let Some(bundle) = self.bundles.get(candidate) else {
+                continue
+            };
+            let Some(pattern) = bundle.get_message(key).and_then(|message| message.value()) else {
+                continue
+            };
+
+            if candidate != language && candidate != primary {
+                self.report(language, key, &format!("using the {candidate} translation instead"));
+            }
+            let mut errors = vec![];
+            let translated = bundle.format_pattern(pattern, args, &mut errors).into_owned();
+            for error in errors {
+                warn!("theme translation \"{key}\" in {candidate}: {error}");
+            }
+            return translated;
+        }
+
+        self.report(language, key, "no catalog defines it");
+        key.to_string()
+    }
+
+    fn language(&self, requested: Option<&str>) -> String {
+        requested.filter(|language| !language.is_empty())
+            .map(str::to_string)
+            .or_else(page_language)
+            .unwrap_or_else(|| self.default_language.clone())
+    }
+
+    fn report(&self, language: &str, key: &str, consequence: &str) {
+        if self.reported.insert((language.to_string(), key.to_string())) {
+            warn!("theme translation \"{key}\" is missing for {language}, {consequence}");
+        }
+    }
+}
+
+fn json_to_fluent(value: &serde_json::Value) -> FluentValue<'static> {
+    match value {
+        serde_json::Value::Number(number) => number.as_f64().map(FluentValue::from).unwrap_or(FluentValue::None),
+        serde_json::Value::String(string) => FluentValue::from(string.clone()),
+        serde_json::Value::Null => FluentValue::None,
+        other => FluentValue::from(other.to_string()),
+    }
+}
+
+fn upon_to_fluent(value: &UponValue) -> FluentValue<'static> {
+    match value {
+        UponValue::Integer(integer) => FluentValue::from(*integer),
+        UponValue::Float(float) => FluentValue::from(*float),
+        UponValue::String(string) => FluentValue::from(string.clone()),
+        UponValue::Bool(boolean) => FluentValue::from(boolean.to_string()),
+        _ => FluentValue::None,
+    }
+}
+
+fn rhai_to_fluent(value: &Dynamic) -> FluentValue<'static> {
+    if let Ok(integer) = value.as_int() {
+        FluentValue::from(integer)
+    } else if let Ok(float) = value.as_float() {
+        FluentValue::from(float)
+    } else {
+        FluentValue::from(value.to_string())
+    }
+}
+
+pub fn register_tera(tera: &mut tera::Tera, translations: Arc<Translations>) {
+    tera.register_function("t", move |args: &HashMap<String, serde_json::Value>| {
+        let key = args.get("key")
+            .and_then(serde_json::Value::as_str)
+            .ok_or_else(|| tera::Error::msg("t() needs a `key` argument"))?;
+        let language = translations.language(args.get("lang").and_then(serde_json::Value::as_str));
+
+        let mut fluent_args = FluentArgs::new();
+        for (name, value) in args.iter().filter(|(name, _)| *name != "key" && *name != "lang") {
+            fluent_args.set(name.clone(), json_to_fluent(value));
+        }
+        Ok(serde_json::Value::String(translations.translate(&language, key, Some(&fluent_args))))
+    });
+}
+
+pub fn register_upon(upon: &mut UponEngine<'static>, translations: Arc<Translations>) {
+    let plain = translations.clone();
+    upon.add_filter("t", move |key: &str, language: &str| {
+        plain.translate(&plain.language(Some(language)), key, None)
+    });
+    upon.add_filter("t_args", move |key: &str, language: &str, args: &BTreeMap<String, UponValue>| {
+        let mut fluent_args = FluentArgs::new();
+        for (name, value) in args {
+            fluent_args.set(name.clone(), upon_to_fluent(value));
+        }
+        translations.translate(&translations.language(Some(language)), key, Some(&fluent_args))
+    });
+}
+
+fn rhai_args(args: &Map) -> FluentArgs<'static> {
+    let mut fluent_args = FluentArgs::new();
+    for (name, value) in args {
+        fluent_args.set(name.to_string(), rhai_to_fluent(value));
+    }
+    fluent_args
+}
+
+pub fn register_rhai(engine: &mut Engine, translations: Arc<Translations>) {
+    let current = translations.clone();
+    engine.register_fn("t", move |key: &str| -> String {
+        current.translate(&current.language(None), key, None)
+    });
+    let current = translations.clone();
+    engine.register_fn("t", move |key: &str, args: Map| -> String {
+        current.translate(&current.language(None), key, Some(&rhai_args(&args)))
+    });
+    let plain = translations.clone();
+    engine.register_fn("t", move |language: &str, key: &str| -> String {
+        plain.translate(&plain.language(Some(language)), key, None)
+    });
+    engine.register_fn("t", move |language: &str, key: &str, args: Map| -> String {
+        translations.translate(&translations.language(Some(language)), key, Some(&rhai_args(&args)))
+    });
+}
@@ -1,12 +1,232 @@
 #![feature(result_flattening)]
 
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use clap::{ArgGroup, Parser, Subcommand};
+use confique::Config;
+use miette::{IntoDiagnostic, WrapErr};
+use sea_orm::DatabaseConnection;
+use tracing::{error, info, warn};
+use ilgi_core::error::{IlgiError, IResult};
+use crate::config::IlgiConfig;
+use crate::sitebuild::{build_site, Site};
+use crate::sitebuild::article::load_articles;
+use crate::theme::{parse_theme, Theme};
+
 mod theme;
 mod config;
 mod file_ops;
 mod sitebuild;
 mod db;
+mod i18n;
+mod formatting;
+mod check;
+mod server;
+
+#[derive(Debug, Parser)]
+#[command(name = "ilgi", version, about)]
+struct Cli {
+    #[arg(short, long, default_value = "ilgi.yml")]
+    config: PathBuf,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    Build {
+        #[arg(short, long, default_value = ".")]
+        source: PathBuf,
+        #[arg(short, long, default_value = "public")]
+        output: PathBuf,
+        #[arg(long)]
+        drafts: bool,
+    },
+    Serve {
+        #[arg(short, long, default_value = ".")]
+        source: PathBuf,
+        #[arg(short, long, default_value = "public")]
+        output: PathBuf,
+        #[arg(short, long, default_value = "127.0.0.1:3000")]
+        address: SocketAddr,
+        #[arg(long)]
+        drafts: bool,
+    },
+    Db {
+        #[command(subcommand)]
+        command: DbCommand,
+    },
+    #[command(group = ArgGroup::new("checks").required(true).multiple(true))]
+    Check {
+        #[arg(long, group = "checks")]
+        links: bool,
+        #[arg(long)]
+        offline: bool,
+        #[arg(short, long, default_value = ".")]
+        source: PathBuf,
+        #[arg(short, long, default_value = "public")]
+        output: PathBuf,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum DbCommand {
+    Migrate {
+        #[arg(long)]
+        steps: Option<u32>,
+    },
+    Status,
+    Rollback {
+        #[arg(long, default_value_t = 1)]
+        steps: u32,
+    },
+}
+
+async fn load_theme(config: &IlgiConfig, source: &Path) -> IResult<Theme> {
+    let theme_name = config.build.theme.as_deref().unwrap_or("default");
+    parse_theme(source.join("themes").join(theme_name), config).await?
+        .load(config)
+        .await
+}
+
+async fn build(config: &IlgiConfig, theme: &Theme, source: &Path, output: &Path) -> IResult<Site> {
+    let staging = output.with_extension("staging");
+    if tokio::fs::metadata(&staging).await.is_ok() {
+        tokio::fs::remove_dir_all(&staging).await.into_diagnostic()?;
+    }
+    let site = build_site(config, theme, source, &staging).await?;
+
+    let previous = output.with_extension("previous");
+    if tokio::fs::metadata(&previous).await.is_ok() {
+        tokio::fs::remove_dir_all(&previous).await.into_diagnostic()?;
+    }
+    if tokio::fs::metadata(output).await.is_ok() {
+        tokio::fs::rename(output, &previous).await.into_diagnostic()?;
+    }
+    tokio::fs::rename(&staging, output).await.into_diagnostic()?;
+    if tokio::fs::metadata(&previous).await.is_ok() {
+        tokio::fs::remove_dir_all(&previous).await.into_diagnostic()?;
+    }
+    Ok(site)
+}
+
+async fn connect_database(config: &IlgiConfig) -> IResult<Option<DatabaseConnection>> {
+    if config.database.url.is_none() {
+        return Ok(None);
+    }
+    let database = db::connect(config).await?;
+    db::ensure_migrated(&database).await?;
+    Ok(Some(database))
+}
+
+async fn store_site(database: &DatabaseConnection, config: &IlgiConfig, site: &Site) -> IResult<()> {
+    if config.build.drafts {
+        warn!("the database is not updated when drafts are built");
+        return Ok(());
+    }
+    db::sync::sync_site(database, config, site).await?;
+    if config.search_api.enabled {
+        db::search::index_articles(database, config, &site.articles).await?;
+    }
+    Ok(())
+}
+
+async fn publish_on_schedule(config: IlgiConfig, theme: Theme, source: PathBuf, output: PathBuf, redirects: server::RedirectTable, database: Option<DatabaseConnection>, mut next_change: Option<DateTime<Utc>>) {
+    while let Some(change) = next_change {
+        let remaining = (change - Utc::now()).to_std().unwrap_or_default();
+        if !remaining.is_zero() {
+            info!("next scheduled rebuild at {change}");
+            tokio::time::sleep(remaining.min(Duration::from_secs(24 * 60 * 60))).await;
+            continue;
+        }
+        match build(&config, &theme, &source, &output).await {
+            Ok(site) => {
+                server::replace_redirects(&redirects, &site.redirects);
+                next_change = site.next_change;
+                if let Some(database) = &database {
+                    if let Err(why) = store_site(database, &config, &site).await {
+                        error!("failed to update the database: {why:?}");
+                    }
+                }
+            }
+            Err(why) => {
+                error!("scheduled rebuild failed: {why:?}");
+                next_change = Some(Utc::now() + chrono::Duration::minutes(5));
+            }
+        }
+    }
+}
 
 #[tokio::main]
-async fn main() {
-    println!("Hello, world!");
+async fn main() -> IResult<()> {
+    tracing_subscriber::fmt::init();
+    let cli = Cli::parse();
+    let config = IlgiConfig::builder()
+        .env()
+        .file(&cli.config)
+        .load()
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to load {}", cli.config.display()))?;
+
+    match cli.command {
+        Command::Build { source, output, drafts } => {
+            let mut config = config;
+            config.build.drafts |= drafts;
+            let theme = load_theme(&config, &source).await?;
+            let site = build(&config, &theme, &source, &output).await?;
+            if let Some(database) = connect_database(&config).await? {
+                store_site(&database, &config, &site).await?;
+            }
+            if let Some(change) = site.next_change {
+                info!("the next scheduled change is at {change}, run ilgi build again then to publish it");
+            }
+        }
+        Command::Serve { source, output, address, drafts } => {
+            let mut config = config;
+            config.build.drafts |= drafts;
+            let theme = load_theme(&config, &source).await?;
+            let site = build(&config, &theme, &source, &output).await?;
+            let redirects = server::redirect_table(&site.redirects);
+            let database = connect_database(&config).await?;
+            if let Some(database) = &database {
+                store_site(database, &config, &site).await?;
+            }
+            let search = match &database {
+                Some(database) if config.search_api.enabled => Some(server::SearchState::new(config.clone(), database.clone(), theme.clone())?),
+                _ => None,
+            };
+            tokio::spawn(publish_on_schedule(config.clone(), theme, source, output.clone(), redirects.clone(), database, site.next_change));
+            server::serve(address, server::router(&output, redirects, search)).await?;
+        }
+        Command::Db { command } => {
+            let database = db::connect(&config).await?;
+            match command {
+                DbCommand::Migrate { steps } => db::migrate(&database, steps).await?,
+                DbCommand::Rollback { steps } => db::rollback(&database, steps).await?,
+                DbCommand::Status => {
+                    for (name, applied) in db::migration_status(&database).await? {
+                        println!("{} {name}", if applied { "applied" } else { "pending" });
+                    }
+                }
+            }
+        }
+        Command::Check { links, offline, source, output } => {
+            if links {
+                let theme = load_theme(&config, &source).await?;
+                let content_dir = source.join(&config.build.content_dir);
+                let (articles, _) = load_articles(&config, &theme, &content_dir, Utc::now())?;
+                let sources = check::source_files(&content_dir, &articles);
+                let problems = check::check_links(&config, &output, &sources, offline).await?;
+                for problem in &problems {
+                    println!("{}:{}: {}: {}", problem.file, problem.line, problem.url, problem.reason);
+                }
+                if !problems.is_empty() {
+                    return Err(IlgiError::BrokenLinks { count: problems.len() }.into());
+                }
+            }
+        }
+    }
+    Ok(())
 }
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use axum::body::Body;
+use axum::extract::{Query, State};
+use axum::http::{header, Request, StatusCode};
+use axum::middleware::{from_fn_with_state, Next};
+use axum::response::{Html, IntoResponse, Json, Response};
+use axum::routing::get;
+use axum::Router;
+use miette::IntoDiagnostic;
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use tower_http::services::ServeDir;
+use tracing::{error, info, instrument};
+use url::form_urlencoded;
+use ilgi_core::error::IResult;
+use crate::config::IlgiConfig;
+use crate::db::search::{search, SearchResults};
+use crate::i18n::with_page_language;
+use crate::sitebuild::percent_decode;
+use crate::sitebuild::redirect::Redirect;
+use crate::theme::Theme;
+
+const DEFAULT_SEARCH_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html lang="{{ site.language }}">
+<head><meta charset="utf-8"><meta name="robots" content="noindex"><title>{{ search.query }} - {{ site.title }}</title></head>
+<body>
+<form action="{{ action }}" method="get"><input type="search" name="q" value="{{ search.query }}"><input type="hidden" name="lang" value="{{ search.language }}"><button>Search</button></form>
+{% for result in search.results %}<article><h2><a href="{{ result.url }}">{{ result.title }}</a></h2><p>{{ result.snippet | safe }}</p></article>
+{% endfor %}{% if previous %}<a rel="prev" href="{{ previous }}">&larr;</a>{% endif %} {% if next %}<a rel="next" href="{{ next }}">&rarr;</a>{% endif %}
+</body>
+</html>
+"#;
+
+#[derive(Clone)]
+pub struct SearchState {
+    config: Arc<IlgiConfig>,
+    db: DatabaseConnection,
+    theme: Arc<Theme>,
+}
+
+impl SearchState {
+    pub fn new(config: IlgiConfig, db: DatabaseConnection, mut theme: Theme) -> IResult<SearchState> {
+        let template = config.search_api.page_template.clone();
+        if theme.upon.get_template(&template).is_none() {
+            theme.upon.add_template(template, DEFAULT_SEARCH_TEMPLATE).into_diagnostic()?;
+        }
+        Ok(SearchState { config: Arc::new(config), db, theme: Arc::new(theme) })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchParams {
+    #[serde(default)]
+    q: String,
+    lang: Option<String>,
+    page: Option<u64>,
+    per_page: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct SearchSite<'a> {
+    title: &'a str,
+    language: &'a str,
+    base_url: &'a str,
+}
+
+#[derive(Serialize)]
+struct SearchPage<'a> {
+    site: SearchSite<'a>,
+    search: &'a SearchResults,
+    action: &'a str,
+    previous: Option<String>,
+    next: Option<String>,
+}
+
+async fn run_search(state: &SearchState, params: &SearchParams) -> Result<SearchResults, StatusCode> {
+    let config = &state.config;
+    let language = params.lang.as_deref().unwrap_or(&config.default_language);
+    if !config.is_language(language) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let per_page = params.per_page.unwrap_or(config.search_api.per_page).clamp(1, config.search_api.max_per_page.max(1));
+    search(&state.db, config, language, params.q.trim(), params.page.unwrap_or(1).max(1), per_page)
+        .await
+        .map_err(|why| {
+            error!("search for {:?} failed: {why:?}", params.q);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+fn page_url(action: &str, results: &SearchResults, page: u64) -> String {
+    let query = form_urlencoded::Serializer::new(String::new())
+        .append_pair("q", &results.query)
+        .append_pair("lang", &results.language)
+        .append_pair("page", &page.to_string())
+        .finish();
+    format!("{action}?{query}")
+}
+
+async fn api_search(State(state): State<SearchState>, Query(params): Query<SearchParams>) -> Response {
+    match run_search(&state, &params).await {
+        Ok(results) => Json(results).into_response(),
+        Err(status) => status.into_response(),
+    }
+}
+
+async fn search_page(State(state): State<SearchState>, Query(params): Query<SearchParams>) -> Response {
+    let results = match run_search(&state, &params).await {
+        Ok(results) => results,
+        Err(status) => return status.into_response(),
+    };
+    let config = &state.config;
+    let action = &config.search_api.page_path;
+    let page = SearchPage {
+        site: SearchSite {
+            title: config.site_title(&results.language),
+            language: &results.language,
+            base_url: &config.base_url,
+        },
+        search: &results,
+        action,
+        previous: (results.page > 1).then(|| page_url(action, &results, results.page - 1)),
+        next: (results.page * results.per_page < results.total).then(|| page_url(action, &results, results.page + 1)),
+    };
+    let rendered = state.theme.upon
+        .get_template(&config.search_api.page_template)
+        .map(|template| with_page_language(Some(&results.language), || template.render(&page).to_string()));
+    match rendered {
+        Some(Ok(html)) => Html(html).into_response(),
+        Some(Err(why)) => {
+            error!("failed to render the search page: {why}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+fn search_router(state: SearchState) -> Router {
+    let page_path = format!("/{}", state.config.search_api.page_path.trim_matches('/'));
+    Router::new()
+        .route("/api/search", get(api_search))
+        .route(&page_path, get(search_page))
+        .route(&format!("{page_path}/"), get(search_page))
+        .with_state(state)
+}
+
+pub type RedirectTable = Arc<RwLock<HashMap<String, Redirect>>>;
+
+fn redirect_map(redirects: &[Redirect]) -> HashMap<String, Redirect> {
+    redirects.iter()
+        .map(|redirect| (redirect.from.trim_end_matches('/').to_string(), redirect.clone()))
+        .collect()
+}
+
+pub fn redirect_table(redirects: &[Redirect]) -> RedirectTable {
+    Arc::new(RwLock::new(redirect_map(redirects)))
+}
+
+pub fn replace_redirects(table: &RedirectTable, redirects: &[Redirect]) {
+    let map = redirect_map(redirects);
+    match table.write() {
+        Ok(mut table) => *table = map,
+        Err(poisoned) => *poisoned.into_inner() = map,
+    }
+}
+
+async fn redirect(State(redirects): State<RedirectTable>, request: Request<Body>, next: Next<Body>) -> Response {
+    let path = percent_decode(request.uri().path());
+    let redirect = redirects.read()
+        .map(|redirects| redirects.get(path.trim_end_matches('/')).cloned())
+        .unwrap_or_default();
+    let Some(redirect) = redirect else {
+        return next.run(request).await
+    };
+    let location = match request.uri().query() {
+        Some(query) if !redirect.to.contains('?') => format!("{}?{query}", redirect.to),
+        _ => redirect.to.clone(),
+    };
+    let status = StatusCode::from_u16(redirect.status).unwrap_or(StatusCode::MOVED_PERMANENTLY);
+    (status, [(header::LOCATION, location)]).into_response()
+}
+
+async fn clean_url(State(output): State<Arc<PathBuf>>, mut request: Request<Body>, next: Next<Body>) -> Response {
+    let path = request.uri().path();
+    let name = path.rsplit('/').next().unwrap_or_default();
+    if !name.is_empty() && !name.contains('.') {
+        let file = output.join(format!("{}.html", percent_decode(path).trim_start_matches('/')));
+        let rewritten = match request.uri().query() {
+            Some(query) => format!("{path}.html?{query}"),
+            None => format!("{path}.html"),
+        };
+        if tokio::fs::metadata(&file).await.is_ok_and(|metadata| metadata.is_file()) {
+            if let Ok(uri) = rewritten.parse() {
+                *request.uri_mut() = uri;
+            }
+        }
+    }
+    next.run(request).await
+}
+
+pub fn router(output: &Path, redirects: RedirectTable, search: Option<SearchState>) -> Router {
+    search.map(search_router)
+        .unwrap_or_else(Router::new)
+        .fallback_service(ServeDir::new(output))
+        .layer(from_fn_with_state(Arc::new(output.to_path_buf()), clean_url))
+        .layer(from_fn_with_state(redirects, redirect))
+}
+
+#[instrument(skip(router))]
+pub async fn serve(address: SocketAddr, router: Router) -> IResult<()> {
+    info!("serving on http://{address}");
+    axum::Server::bind(&address)
+        .serve(router.into_make_service())
+        .await
+        .into_diagnostic()
+}
@@ -0,0 +1,326 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate};
+use miette::WrapErr;
+use serde::Serialize;
+use ilgi_core::error::IResult;
+use crate::config::IlgiConfig;
+use crate::file_ops::write_output;
+use crate::formatting::format_date;
+use crate::sitebuild::absolute_url;
+use crate::sitebuild::article::Article;
+use crate::sitebuild::locale::locale_for;
+use crate::sitebuild::pagination::paginate;
+use crate::sitebuild::permalink::styled_path;
+use crate::sitebuild::render::{base_context, output_file, render_template_or};
+use crate::sitebuild::sitemap::SitemapEntry;
+use crate::sitebuild::Site;
+use crate::theme::Theme;
+
+pub const ARCHIVE_TEMPLATE: &str = "archive.html";
+pub const YEAR_TEMPLATE: &str = "archive_year.html";
+pub const MONTH_TEMPLATE: &str = "archive_month.html";
+
+const DEFAULT_ARCHIVE_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html lang="{{ site.language }}">
+<head><meta charset="utf-8"><title>{{ site.title }}</title></head>
+<body>
+{% for year in archive %}
+<h2><a href="{{ year.permalink }}">{{ year.year }}</a></h2>
+{% for month in year.months %}
+<h3><a href="{{ month.permalink }}">{{ month.name }}</a></h3>
+<ul>{% for entry in month.entries %}<li><time datetime="{{ entry.date }}">{{ entry.formatted_date }}</time> <a href="{{ entry.permalink }}" hreflang="{{ entry.language }}">{{ entry.title }}</a></li>{% endfor %}</ul>
+{% endfor %}
+{% endfor %}
+{% if paginator.total > 1 %}<nav>{% if paginator.previous %}<a rel="prev" href="{{ paginator.previous }}">&larr;</a>{% endif %} {{ paginator.current }} / {{ paginator.total }} {% if paginator.next %}<a rel="next" href="{{ paginator.next }}">&rarr;</a>{% endif %}</nav>{% endif %}
+</body>
+</html>
+"#;
+
+const DEFAULT_YEAR_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html lang="{{ site.language }}">
+<head><meta charset="utf-8"><title>{{ year.year }} - {{ site.title }}</title></head>
+<body>
+<h1>{{ year.year }}</h1>
+{% for month in year.months %}
+<h2><a href="{{ month.permalink }}">{{ month.name }}</a></h2>
+<ul>{% for entry in month.entries %}<li><time datetime="{{ entry.date }}">{{ entry.formatted_date }}</time> <a href="{{ entry.permalink }}" hreflang="{{ entry.language }}">{{ entry.title }}</a></li>{% endfor %}</ul>
+{% endfor %}
+{% if paginator.total > 1 %}<nav>{% if paginator.previous %}<a rel="prev" href="{{ paginator.previous }}">&larr;</a>{% endif %} {{ paginator.current }} / {{ paginator.total }} {% if paginator.next %}<a rel="next" href="{{ paginator.next }}">&rarr;</a>{% endif %}</nav>{% endif %}
+</body>
+</html>
+"#;
+
+const DEFAULT_MONTH_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html lang="{{ site.language }}">
+<head><meta charset="utf-8"><title>{{ month.name }} - {{ site.title }}</title></head>
+<body>
+<h1>{{ month.name }}</h1>
+<ul>{% for entry in month.entries %}<li><time datetime="{{ entry.date }}">{{ entry.formatted_date }}</time> <a href="{{ entry.permalink }}" hreflang="{{ entry.language }}">{{ entry.title }}</a></li>{% endfor %}</ul>
+<p><a href="{{ year.permalink }}">{{ year.year }}</a></p>
+{% if paginator.total > 1 %}<nav>{% if paginator.previous %}<a rel="prev" href="{{ paginator.previous }}">&larr;</a>{% endif %} {{ paginator.current }} / {{ paginator.total }} {% if paginator.next %}<a rel="next" href="{{ paginator.next }}">&rarr;</a>{% endif %}</nav>{% endif %}
+</body>
+</html>
+"#;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ArchiveEntry<'a> {
+    pub title: &'a str,
+    pub path: &'a str,
+    pub permalink: &'a str,
+    pub language: &'a str,
+    pub date: DateTime<FixedOffset>,
+    pub formatted_date: String,
+    #[serde(skip)]
+    pub article: &'a Article,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ArchiveMonth<'a> {
+    pub year: i32,
+    pub month: u32,
+    pub name: String,
+    pub path: String,
+    pub permalink: String,
+    pub entries: Vec<ArchiveEntry<'a>>,
+    #[serde(skip)]
+    pub articles: Vec<&'a Article>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ArchiveYear<'a> {
+    pub year: i32,
+    pub path: String,
+    pub permalink: String,
+    pub count: usize,
+    pub months: Vec<ArchiveMonth<'a>>,
+    #[serde(skip)]
+    pub articles: Vec<&'a Article>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Archive<'a> {
+    pub language: String,
+    pub path: String,
+    pub permalink: String,
+    pub years: Vec<ArchiveYear<'a>>,
+    #[serde(skip)]
+    pub articles: Vec<&'a Article>,
+}
+
+pub fn collect_archive<'a>(config: &IlgiConfig, language: &str, articles: &[&'a Article]) -> IResult<Archive<'a>> {
+    let mut dated = articles.iter().copied().filter(|article| article.date.is_some()).collect::<Vec<_>>();
+    dated.sort_by(|a, b| b.date.cmp(&a.date).then_with(|| a.path.cmp(&b.path)));
+    let path = styled_path(config, &format!("{}/{}/", config.language_prefix(language), config.build.archive.path.trim_matches('/')));
+    Ok(Archive {
+        language: language.to_string(),
+        permalink: absolute_url(config, &path),
+        path,
+        years: group_archive(config, language, &dated)?,
+        articles: dated,
+    })
+}
+
+fn group_archive<'a>(config: &IlgiConfig, language: &str, articles: &[&'a Article]) -> IResult<Vec<ArchiveYear<'a>>> {
+    let locale = locale_for(language);
+    let prefix = config.language_prefix(language);
+    let timezone = config.tz()?;
+
+    let mut grouped = BTreeMap::<(i32, u32), Vec<ArchiveEntry<'a>>>::new();
+    for article in articles.iter().copied() {
+        let Some(date) = article.date else {
+            continue
+        };
+        let local = date.with_timezone(&timezone);
+        grouped.entry((local.year(), local.month())).or_default().push(ArchiveEntry {
+            title: &article.title,
+            path: &article.path,
+            permalink: &article.permalink,
+            language: &article.language,
+            date,
+            formatted_date: format_date(&date, &timezone, &config.build.archive.date_format, language),
+            article,
+        });
+    }
+
+    let mut years = BTreeMap::<i32, Vec<ArchiveMonth<'a>>>::new();
+    for ((year, month), mut entries) in grouped.into_iter().rev() {
+        entries.sort_by(|a, b| b.date.cmp(&a.date).then_with(|| a.path.cmp(b.path)));
+        let path = styled_path(config, &format!("{prefix}/{year}/{month:02}/"));
+        let name = NaiveDate::from_ymd_opt(year, month, 1)
+            .map(|first| first.format_localized(&config.build.archive.month_format, locale).to_string())
+            .unwrap_or_else(|| format!("{year}-{month:02}"));
+        years.entry(year).or_default().push(ArchiveMonth {
+            year,
+            month,
+            name,
+            permalink: absolute_url(config, &path),
+            path,
+            articles: entries.iter().map(|entry| entry.article).collect(),
+            entries,
+        });
+    }
+
+    let archive = years.into_iter()
+        .rev()
+        .map(|(year, months)| {
+            let path = styled_path(config, &format!("{prefix}/{year}/"));
+            ArchiveYear {
+                year,
+                permalink: absolute_url(config, &path),
+                path,
+                count: months.iter().map(|month| month.entries.len()).sum(),
+                articles: months.iter().flat_map(|month| month.articles.iter().copied()).collect(),
+                months,
+            }
+        })
+        .collect();
+    Ok(archive)
+}
+
+pub fn sitemap_entries<'a>(site: &'a Site, archive: &'a Archive<'a>) -> impl Iterator<Item = SitemapEntry> + 'a {
+    let index = SitemapEntry {
+        loc: archive.permalink.clone(),
+        lastmod: latest(site, archive.years.iter().flat_map(|year| year.months.iter().flat_map(|month| month.entries.iter()))),
+        alternates: vec![],
+    };
+    std::iter::once(index).chain(archive.years.iter().flat_map(move |year| {
+        std::iter::once(SitemapEntry {
+            loc: year.permalink.clone(),
+            lastmod: latest(site, year.months.iter().flat_map(|month| month.entries.iter())),
+            alternates: vec![],
+        })
+        .chain(year.months.iter().map(move |month| SitemapEntry {
+            loc: month.permalink.clone(),
+            lastmod: latest(site, month.entries.iter()),
+            alternates: vec![],
+        }))
+    }))
+}
+
+fn latest<'a>(site: &Site, entries: impl Iterator<Item = &'a ArchiveEntry<'a>>) -> Option<DateTime<FixedOffset>> {
+    entries.filter_map(|entry| site.last_modified(entry.article)).max()
+}
+
+fn page_archive<'a>(config: &IlgiConfig, archive: &Archive<'a>, page: &[&'a Article]) -> IResult<Vec<ArchiveYear<'a>>> {
+    let mut years = group_archive(config, &archive.language, page)?;
+    for year in &mut years {
+        year.count = archive.years.iter().find(|full| full.year == year.year).map_or(year.count, |full| full.count);
+    }
+    Ok(years)
+}
+
+pub async fn write_archive(config: &IlgiConfig, theme: &Theme, output: &Path, archive: &Archive<'_>) -> IResult<()> {
+    let per_page = config.build.archive.paginate_by;
+    let language = &archive.language;
+    for paginator in paginate(config, &archive.path, &archive.articles, per_page) {
+        let mut context = base_context(config, language);
+        context.insert("archive", &page_archive(config, archive, paginator.pages)?);
+        context.insert("paginator", &paginator);
+        let html = render_template_or(theme, ARCHIVE_TEMPLATE, DEFAULT_ARCHIVE_TEMPLATE, &context)
+            .wrap_err_with(|| format!("while rendering archive page {} ({language})", paginator.current))?;
+        write_output(output, output_file(&paginator.path), html).await?;
+    }
+
+    for year in &archive.years {
+        for paginator in paginate(config, &year.path, &year.articles, per_page) {
+            let mut context = base_context(config, language);
+            context.insert("year", &page_archive(config, archive, paginator.pages)?.first());
+            context.insert("paginator", &paginator);
+            let html = render_template_or(theme, YEAR_TEMPLATE, DEFAULT_YEAR_TEMPLATE, &context)
+                .wrap_err_with(|| format!("while rendering the {} archive ({language})", year.year))?;
+            write_output(output, output_file(&paginator.path), html).await?;
+        }
+
+        for month in &year.months {
+            for paginator in paginate(config, &month.path, &month.articles, per_page) {
+                let mut context = base_context(config, language);
+                context.insert("year", year);
+                context.insert("month", &group_archive(config, language, paginator.pages)?.first().and_then(|year| year.months.first()));
+                context.insert("paginator", &paginator);
+                let html = render_template_or(theme, MONTH_TEMPLATE, DEFAULT_MONTH_TEMPLATE, &context)
+                    .wrap_err_with(|| format!("while rendering the {}-{:02} archive ({language})", month.year, month.month))?;
+                write_output(output, output_file(&paginator.path), html).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::test_config;
+    use super::*;
+
+    fn article(path: &str, date: Option<&str>) -> Article {
+        Article {
+            path: path.to_string(),
+            date: date.map(|date| DateTime::parse_from_rfc3339(date).unwrap()),
+            ..Default::default()
+        }
+    }
+
+    fn config() -> IlgiConfig {
+        let mut config = test_config();
+        config.timezone = "UTC".to_string();
+        config.build.archive.path = "archive".to_string();
+        config.build.archive.month_format = "%B %Y".to_string();
+        config
+    }
+
+    fn months<'a>(archive: &Archive<'a>) -> Vec<(i32, u32, Vec<&'a str>)> {
+        archive.years.iter()
+            .flat_map(|year| year.months.iter())
+            .map(|month| (month.year, month.month, month.entries.iter().map(|entry| entry.path).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn groups_newest_first_and_skips_undated_articles() {
+        let articles = [
+            article("/a/", Some("2023-03-01T10:00:00Z")),
+            article("/b/", Some("2024-01-05T10:00:00Z")),
+            article("/c/", None),
+            article("/d/", Some("2023-03-20T10:00:00Z")),
+            article("/e/", Some("2023-03-20T10:00:00Z")),
+        ];
+        let articles = articles.iter().collect::<Vec<_>>();
+        let archive = collect_archive(&config(), "en", &articles).unwrap();
+
+        assert_eq!(archive.path, "/archive/");
+        assert_eq!(months(&archive), [
+            (2024, 1, vec!["/b/"]),
+            (2023, 3, vec!["/d/", "/e/", "/a/"]),
+        ]);
+        assert_eq!(archive.years.iter().map(|year| (year.year, year.count)).collect::<Vec<_>>(), [(2024, 1), (2023, 3)]);
+        assert_eq!(archive.years[1].path, "/2023/");
+        assert_eq!(archive.years[1].months[0].path, "/2023/03/");
+        assert_eq!(archive.years[1].months[0].name, "March 2023");
+        assert_eq!(archive.articles.len(), 4);
+    }
+
+    #[test]
+    fn months_follow_the_site_timezone() {
+        let articles = [article("/late/", Some("2023-12-31T23:30:00Z"))];
+        let articles = articles.iter().collect::<Vec<_>>();
+        assert_eq!(months(&collect_archive(&config(), "en", &articles).unwrap()), [(2023, 12, vec!["/late/"])]);
+
+        let mut config = config();
+        config.timezone = "Asia/Seoul".to_string();
+        assert_eq!(months(&collect_archive(&config, "en", &articles).unwrap()), [(2024, 1, vec!["/late/"])]);
+    }
+
+    #[test]
+    fn pages_keep_the_full_year_counts() {
+        let articles = [
+            article("/a/", Some("2023-05-01T10:00:00Z")),
+            article("/b/", Some("2023-04-01T10:00:00Z")),
+        ];
+        let articles = articles.iter().collect::<Vec<_>>();
+        let config = config();
+        let archive = collect_archive(&config, "en", &articles).unwrap();
+        let years = page_archive(&config, &archive, &articles[..1]).unwrap();
+        assert_eq!(years[0].months.len(), 1);
+        assert_eq!(years[0].count, 2);
+    }
+}
@@ -0,0 +1,373 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveTime, Offset as _, TimeZone, Utc};
+use chrono_tz::Tz;
+use miette::{IntoDiagnostic, Report, WrapErr};
+use rayon::prelude::*;
+use relative_path::RelativePath;
+use serde::{Deserialize, Serialize};
+use toml::value::{Datetime, Offset};
+use tracing::{instrument, warn};
+use ilgi_core::error::{IlgiError, IResult};
+use crate::config::IlgiConfig;
+use crate::file_ops::load_dir;
+use crate::formatting::TextStats;
+use crate::sitebuild::absolute_url;
+use crate::sitebuild::links::{resolve_links, ArticleLink};
+use crate::sitebuild::markdown::{render_markdown, InternalLink, MarkdownError, RenderedMarkdown, TocEntry};
+use crate::sitebuild::section::{is_section_index, SectionLink};
+use crate::sitebuild::series::ArticleSeries;
+use crate::sitebuild::permalink::{article_path, check_duplicate_urls};
+use crate::sitebuild::slug::url_slug;
+use crate::sitebuild::summary::summarize;
+use crate::theme::Theme;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Author {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avatar: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub url: String,
+    pub mime_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size_in_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_in_seconds: Option<f64>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FrontMatter {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub slug: Option<String>,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub translation_key: Option<String>,
+    #[serde(default)]
+    pub date: Option<Datetime>,
+    #[serde(default)]
+    pub updated: Option<Datetime>,
+    #[serde(default)]
+    pub expires: Option<Datetime>,
+    #[serde(default)]
+    pub draft: bool,
+    #[serde(default)]
+    pub authors: Vec<Author>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub categories: Vec<String>,
+    #[serde(default)]
+    pub taxonomies: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    #[serde(default)]
+    pub weight: Option<i64>,
+    #[serde(default)]
+    pub search: Option<bool>,
+    #[serde(default)]
+    pub series: Option<String>,
+    #[serde(default)]
+    pub series_order: Option<u32>,
+    #[serde(default)]
+    pub extra: toml::Table,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Article {
+    pub source: String,
+    pub slug: String,
+    pub language: String,
+    pub translation_key: String,
+    pub path: String,
+    pub permalink: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub date: Option<DateTime<FixedOffset>>,
+    pub updated: Option<DateTime<FixedOffset>>,
+    pub expires: Option<DateTime<FixedOffset>>,
+    pub draft: bool,
+    pub published: bool,
+    pub expired: bool,
+    pub authors: Vec<Author>,
+    pub taxonomies: BTreeMap<String, Vec<String>>,
+    pub attachments: Vec<Attachment>,
+    pub aliases: Vec<String>,
+    pub weight: Option<i64>,
+    pub searchable: bool,
+    #[serde(skip)]
+    pub series_name: Option<String>,
+    pub series_order: Option<u32>,
+    pub series: Option<ArticleSeries>,
+    pub extra: toml::Table,
+    #[serde(skip)]
+    pub front_matter: toml::Table,
+    #[serde(skip)]
+    pub raw_content: String,
+    pub content: String,
+    pub summary: String,
+    pub summary_text: String,
+    pub has_more: bool,
+    pub word_count: usize,
+    pub reading_minutes: usize,
+    pub toc: Vec<TocEntry>,
+    #[serde(skip)]
+    pub links: Vec<InternalLink>,
+    pub backlinks: Vec<ArticleLink>,
+    pub related: Vec<ArticleLink>,
+    pub ancestors: Vec<SectionLink>,
+    pub previous: Option<ArticleLink>,
+    pub next: Option<ArticleLink>,
+    #[serde(skip)]
+    pub template: Option<String>,
+}
+
+impl Article {
+    pub fn parse(config: &IlgiConfig, theme: &Theme, source: &RelativePath, data: &str) -> IResult<Article> {
+        let (front_matter, body) = split_front_matter(data)
+            .ok_or_else(|| IlgiError::MissingFrontMatter { path: source.to_string() })?;
+        let raw_front_matter = toml::from_str::<toml::Table>(front_matter)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("invalid front matter in {source}"))?;
+        let front_matter = toml::from_str::<FrontMatter>(front_matter)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("invalid front matter in {source}"))?;
+        let timezone = config.tz()?;
+
+        let parent = source.parent().map(RelativePath::as_str).unwrap_or_default();
+        let stem = source.file_stem().unwrap_or_default();
+        let (stem, suffix_language) = match stem.rsplit_once('.') {
+            Some((stem, suffix)) if config.is_language(suffix) => (stem, Some(suffix)),
+            _ => (stem, None),
+        };
+
+        let language = front_matter.language.clone()
+            .or_else(|| suffix_language.map(str::to_string))
+            .unwrap_or_else(|| config.default_language.clone());
+        if !config.is_language(&language) {
+            warn!("{source} is written in \"{language}\", which is not a configured language");
+        }
+        let slug = match &front_matter.slug {
+            Some(slug) => slug.clone(),
+            None => match url_slug(&config.build.permalinks, stem) {
+                slug if slug.is_empty() => stem.to_string(),
+                slug => slug,
+            },
+        };
+        let translation_key = match &front_matter.translation_key {
+            Some(key) => key.clone(),
+            None if parent.is_empty() => stem.to_string(),
+            None => format!("{parent}/{stem}"),
+        };
+
+        let mut taxonomies = front_matter.taxonomies;
+        if !front_matter.tags.is_empty() {
+            taxonomies.entry("tags".to_string()).or_default().extend(front_matter.tags);
+        }
+        if !front_matter.categories.is_empty() {
+            taxonomies.entry("categories".to_string()).or_default().extend(front_matter.categories);
+        }
+        let rendered = render_content(config, theme, source, data, body)?;
+
+        let mut article = Article {
+            source: source.to_string(),
+            slug,
+            language,
+            translation_key,
+            path: String::new(),
+            permalink: String::new(),
+            title: front_matter.title.unwrap_or_else(|| stem.to_string()),
+            description: front_matter.description,
+            date: front_matter.date.as_ref().and_then(|date| datetime_to_chrono(date, &timezone)),
+            updated: front_matter.updated.as_ref().and_then(|updated| datetime_to_chrono(updated, &timezone)),
+            expires: front_matter.expires.as_ref().and_then(|expires| datetime_to_chrono(expires, &timezone)),
+            draft: front_matter.draft,
+            published: true,
+            expired: false,
+            authors: front_matter.authors,
+            taxonomies,
+            attachments: front_matter.attachments,
+            aliases: front_matter.aliases,
+            weight: front_matter.weight,
+            searchable: front_matter.search.unwrap_or(true),
+            series_name: front_matter.series.filter(|series| !series.trim().is_empty()),
+            series_order: front_matter.series_order,
+            series: None,
+            extra: front_matter.extra,
+            front_matter: raw_front_matter,
+            content: rendered.html,
+            summary: String::new(),
+            summary_text: String::new(),
+            has_more: false,
+            word_count: 0,
+            reading_minutes: 0,
+            toc: rendered.toc,
+            links: rendered.links,
+            backlinks: vec![],
+            related: vec![],
+            ancestors: vec![],
+            previous: None,
+            next: None,
+            template: None,
+            raw_content: body.to_string(),
+        };
+        article.path = article_path(config, &timezone, &article)?;
+        article.permalink = absolute_url(config, &article.path);
+        Ok(article)
+    }
+
+    pub fn link(&self) -> ArticleLink {
+        ArticleLink {
+            title: self.title.clone(),
+            language: self.language.clone(),
+            path: self.path.clone(),
+            permalink: self.permalink.clone(),
+        }
+    }
+
+    pub fn terms(&self, taxonomy: &str) -> &[String] {
+        self.taxonomies.get(taxonomy).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn last_modified(&self) -> Option<DateTime<FixedOffset>> {
+        self.updated.or(self.date)
+    }
+
+    pub fn is_scheduled(&self, now: DateTime<Utc>) -> bool {
+        self.date.is_some_and(|date| date.with_timezone(&Utc) > now)
+    }
+
+    pub fn summarize(&mut self, words: usize) {
+        let (content, summary) = summarize(&self.content, words);
+        let stats = TextStats::of_html(&content);
+        self.content = content;
+        self.summary = summary.html;
+        self.summary_text = summary.text;
+        self.has_more = summary.has_more;
+        self.word_count = stats.word_count();
+        self.reading_minutes = stats.reading_minutes();
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires.is_some_and(|expires| expires.with_timezone(&Utc) <= now)
+    }
+}
+
+#[instrument(skip(config, theme))]
+pub fn load_articles(config: &IlgiConfig, theme: &Theme, directory: &Path, now: DateTime<Utc>) -> IResult<(Vec<Article>, Option<DateTime<Utc>>)> {
+    let sources = load_dir(directory)?
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "md") && !is_section_index(path))
+        .collect::<Vec<_>>();
+
+    let mut articles = sources.into_par_iter()
+        .map(|path| {
+            let relative = RelativePath::from_path(path.strip_prefix(directory).into_diagnostic()?)
+                .into_diagnostic()?
+                .to_relative_path_buf();
+            let data = std::fs::read_to_string(&path).into_diagnostic()?;
+            Article::parse(config, theme, &relative, &data)
+        })
+        .collect::<IResult<Vec<Article>>>()?;
+
+    let next_change = articles.iter()
+        .filter(|article| !article.draft)
+        .flat_map(|article| [article.date, article.expires])
+        .flatten()
+        .map(|time| time.with_timezone(&Utc))
+        .filter(|time| *time > now)
+        .min();
+    for article in &mut articles {
+        article.expired = article.is_expired(now);
+        article.published = !article.draft && !article.is_scheduled(now) && !article.expired;
+    }
+
+    articles.sort_by(|a, b| b.date.cmp(&a.date).then_with(|| a.source.cmp(&b.source)));
+    let is_listed = |article: &Article| config.build.drafts || (!article.draft && !article.is_scheduled(now) && (!article.expired || config.build.keep_expired));
+    let listed = articles.iter().map(is_listed).collect::<Vec<_>>();
+    resolve_links(&mut articles, &listed)?;
+    articles.retain(is_listed);
+    check_duplicate_urls(articles.iter().map(|article| (article.path.clone(), article.source.clone())))?;
+    articles.par_iter_mut().for_each(|article| article.summarize(config.build.markdown.summary_words));
+    Ok((articles, next_change))
+}
+
+pub fn render_content(config: &IlgiConfig, theme: &Theme, source: &RelativePath, data: &str, body: &str) -> IResult<RenderedMarkdown> {
+    render_markdown(&config.build.markdown, theme, body)
+        .map_err(|why| match why {
+            MarkdownError::Math(why) => {
+                let offset = data.len() - body.len() + why.offset;
+                Report::from(IlgiError::InvalidMath {
+                    path: source.to_string(),
+                    line: data[..offset].matches('\n').count() + 1,
+                    message: why.message,
+                    document: data.to_string(),
+                    span: (offset, why.len).into(),
+                })
+            }
+            MarkdownError::Template(why) => why.wrap_err(format!("while rendering the Markdown in {source}")),
+        })
+}
+
+pub fn split_front_matter(data: &str) -> Option<(&str, &str)> {
+    let rest = data.trim_start_matches('\u{feff}').strip_prefix("+++")?;
+    let rest = rest.strip_prefix("\r\n").or_else(|| rest.strip_prefix('\n'))?;
+    let (front_matter, body) = match rest.strip_prefix("+++") {
+        Some(body) => ("", body),
+        None => rest.find("\n+++").map(|end| (&rest[..end], &rest[end + 4..]))?,
+    };
+    let body = body.strip_prefix("\r\n")
+        .or_else(|| body.strip_prefix('\n'))
+        .unwrap_or(body);
+    Some((front_matter, body))
+}
+
+pub fn datetime_to_chrono(datetime: &Datetime, timezone: &Tz) -> Option<DateTime<FixedOffset>> {
+    let date = datetime.date?;
+    let date = NaiveDate::from_ymd_opt(date.year as i32, date.month as u32, date.day as u32)?;
+    let time = match datetime.time {
+        Some(time) => NaiveTime::from_hms_nano_opt(time.hour as u32, time.minute as u32, time.second as u32, time.nanosecond)?,
+        None => NaiveTime::MIN,
+    };
+    let offset = match datetime.offset {
+        Some(Offset::Custom { minutes }) => FixedOffset::east_opt(minutes as i32 * 60)?,
+        Some(Offset::Z) => FixedOffset::east_opt(0)?,
+        None => timezone.offset_from_local_datetime(&date.and_time(time)).earliest()?.fix(),
+    };
+    offset.from_local_datetime(&date.and_time(time)).single()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_front_matter() {
+        assert_eq!(split_front_matter("+++\ntitle = \"Hi\"\n+++\nBody\n"), Some(("title = \"Hi\"", "Body\n")));
+        assert_eq!(split_front_matter("\u{feff}+++\r\ntitle = \"Hi\"\n+++\r\nBody"), Some(("title = \"Hi\"", "Body")));
+        assert_eq!(split_front_matter("No front matter"), None);
+        assert_eq!(split_front_matter("+++\ntitle = \"Unclosed\"\n"), None);
+    }
+
+    #[test]
+    fn accepts_an_empty_front_matter_block() {
+        let (front_matter, body) = split_front_matter("+++\n+++\nBody\n").unwrap();
+        assert_eq!((front_matter, body), ("", "Body\n"));
+        let front_matter = toml::from_str::<FrontMatter>(front_matter).unwrap();
+        assert_eq!(front_matter.title, None);
+        assert!(!front_matter.draft);
+    }
+}
@@ -0,0 +1,98 @@
+use chrono::{DateTime, FixedOffset};
+use miette::IntoDiagnostic;
+use serde::Serialize;
+use ilgi_core::error::IResult;
+use crate::sitebuild::article::Author;
+use crate::sitebuild::feed::{Feed, FeedAttachment, FeedEntry};
+
+pub const FILE_NAME: &str = "feed.json";
+pub const VERSION: &str = "https://jsonfeed.org/version/1.1";
+
+#[derive(Serialize)]
+struct JsonFeed<'a> {
+    version: &'static str,
+    title: &'a str,
+    home_page_url: &'a str,
+    feed_url: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    language: &'a str,
+    items: Vec<JsonFeedItem<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonFeedItem<'a> {
+    id: &'a str,
+    url: &'a str,
+    title: &'a str,
+    content_html: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date_published: Option<DateTime<FixedOffset>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date_modified: Option<DateTime<FixedOffset>>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    authors: &'a [Author],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    tags: &'a [String],
+    language: &'a str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    attachments: Vec<JsonFeedAttachment<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonFeedAttachment<'a> {
+    url: &'a str,
+    mime_type: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size_in_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration_in_seconds: Option<f64>,
+}
+
+impl<'a> From<&'a FeedEntry<'a>> for JsonFeedItem<'a> {
+    fn from(entry: &'a FeedEntry<'a>) -> Self {
+        JsonFeedItem {
+            id: entry.id,
+            url: entry.url,
+            title: entry.title,
+            content_html: entry.content_html,
+            summary: entry.summary,
+            date_published: entry.published,
+            date_modified: entry.updated,
+            authors: entry.authors,
+            tags: entry.tags,
+            language: entry.language,
+            attachments: entry.attachments.iter().map(JsonFeedAttachment::from).collect(),
+        }
+    }
+}
+
+impl<'a> From<&'a FeedAttachment<'a>> for JsonFeedAttachment<'a> {
+    fn from(attachment: &'a FeedAttachment<'a>) -> Self {
+        JsonFeedAttachment {
+            url: &attachment.url,
+            mime_type: attachment.mime_type,
+            title: attachment.title,
+            size_in_bytes: attachment.size_in_bytes,
+            duration_in_seconds: attachment.duration_in_seconds,
+        }
+    }
+}
+
+pub fn render_json_feed(feed: &Feed) -> IResult<String> {
+    let json_feed = JsonFeed {
+        version: VERSION,
+        title: feed.title,
+        home_page_url: &feed.home_page_url,
+        feed_url: &feed.feed_url,
+        description: feed.description,
+        language: feed.language,
+        items: feed.entries.iter().map(JsonFeedItem::from).collect(),
+    };
+
+    serde_json::to_string(&json_feed).into_diagnostic()
+}
@@ -0,0 +1,109 @@
+use std::path::Path;
+use chrono::{DateTime, FixedOffset};
+use ilgi_core::error::IResult;
+use crate::config::IlgiConfig;
+use crate::file_ops::write_output;
+use crate::sitebuild::absolute_url;
+use crate::sitebuild::article::{Article, Author};
+use crate::sitebuild::permalink::directory_path;
+
+pub mod json;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Feed<'a> {
+    pub title: &'a str,
+    pub description: Option<&'a str>,
+    pub language: &'a str,
+    pub home_page_url: String,
+    pub feed_url: String,
+    pub updated: Option<DateTime<FixedOffset>>,
+    pub entries: Vec<FeedEntry<'a>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FeedEntry<'a> {
+    pub id: &'a str,
+    pub url: &'a str,
+    pub title: &'a str,
+    pub content_html: &'a str,
+    pub summary: Option<&'a str>,
+    pub published: Option<DateTime<FixedOffset>>,
+    pub updated: Option<DateTime<FixedOffset>>,
+    pub language: &'a str,
+    pub authors: &'a [Author],
+    pub tags: &'a [String],
+    pub attachments: Vec<FeedAttachment<'a>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FeedAttachment<'a> {
+    pub url: String,
+    pub mime_type: &'a str,
+    pub title: Option<&'a str>,
+    pub size_in_bytes: Option<u64>,
+    pub duration_in_seconds: Option<f64>,
+}
+
+impl<'a> Feed<'a> {
+    pub fn new(config: &'a IlgiConfig, directory: &str, language: &'a str, file_name: &str, articles: impl IntoIterator<Item = &'a Article>) -> Feed<'a> {
+        let mut articles = articles.into_iter().collect::<Vec<_>>();
+        articles.sort_by(|a, b| b.date.cmp(&a.date).then_with(|| a.source.cmp(&b.source)));
+        articles.truncate(config.serve.feed_limit);
+
+        let entries = articles.into_iter()
+            .map(|article| FeedEntry::new(config, article))
+            .collect::<Vec<_>>();
+
+        let directory = directory_path(directory);
+        Feed {
+            title: config.site_title(language),
+            description: config.site_description(language),
+            language,
+            home_page_url: absolute_url(config, &directory),
+            feed_url: absolute_url(config, &format!("{directory}{file_name}")),
+            updated: entries.iter().filter_map(|entry| entry.updated.or(entry.published)).max(),
+            entries,
+        }
+    }
+}
+
+impl<'a> FeedEntry<'a> {
+    pub fn new(config: &IlgiConfig, article: &'a Article) -> FeedEntry<'a> {
+        FeedEntry {
+            id: &article.permalink,
+            url: &article.permalink,
+            title: &article.title,
+            content_html: match config.serve.feed_summaries && article.has_more {
+                true => &article.summary,
+                false => &article.content,
+            },
+            summary: article.description.as_deref().or(Some(article.summary_text.as_str()).filter(|summary| !summary.is_empty())),
+            published: article.date,
+            updated: article.updated,
+            language: &article.language,
+            authors: &article.authors,
+            tags: article.terms("tags"),
+            attachments: article.attachments.iter()
+                .map(|attachment| FeedAttachment {
+                    url: absolute_url(config, &attachment.url),
+                    mime_type: &attachment.mime_type,
+                    title: attachment.title.as_deref(),
+                    size_in_bytes: attachment.size_in_bytes,
+                    duration_in_seconds: attachment.duration_in_seconds,
+                })
+                .collect(),
+        }
+    }
+}
+
+pub async fn write_feeds<'a>(config: &'a IlgiConfig, output: &Path, directory: &str, language: &'a str, articles: impl IntoIterator<Item = &'a Article>) -> IResult<()> {
+    let directory = directory_path(directory);
+    let articles = articles.into_iter().collect::<Vec<_>>();
+
+    if config.serve.json_feed {
+        let feed = Feed::new(config, &directory, language, json::FILE_NAME, articles.iter().copied());
+        write_output(output, format!("{directory}{}", json::FILE_NAME), json::render_json_feed(&feed)?).await?;
+    }
+
+    Ok(())
+}
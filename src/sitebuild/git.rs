@@ -1,6 +1,10 @@
+use std::collections::HashMap;
 use std::path::Path;
+use chrono::{DateTime, FixedOffset};
 use gix::remote::fetch::Shallow;
 use tempfile::tempdir_in;
+use tokio::process::Command;
+use tracing::warn;
 use ilgi_core::error::IResult;
 use crate::config::IlgiConfig;
 
@@ -12,7 +16,7 @@ pub async fn clone_git(config: &IlgiConfig, dir: impl AsRef<Path>) -> IResult<()
         tempdir.path()
     )?;
 
-    fetch.with_shallow(Shallow::DepthAtRemote(2.into()))
+    fetch.with_shallow(Shallow::NoChange)
         .with_fetch_options(Options::default())
         .configure_connection(|connection| {
             connection.set_credentials(|cred| {
@@ -20,4 +24,76 @@ pub async fn clone_git(config: &IlgiConfig, dir: impl AsRef<Path>) -> IResult<()
             })
         })
 
-}
\ No newline at end of file
+}
+
+pub async fn head_commit(repository: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repository)
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .await;
+
+    match output {
+        Ok(output) if output.status.success() => Some(String::from_utf8_lossy(&output.stdout).trim().to_string()),
+        Ok(output) => {
+            warn!("could not read the commit of {}: {}", repository.display(), String::from_utf8_lossy(&output.stderr).trim());
+            None
+        }
+        Err(why) => {
+            warn!("could not run git in {}: {why}", repository.display());
+            None
+        }
+    }
+}
+
+async fn is_shallow(repository: &Path) -> bool {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repository)
+        .args(["rev-parse", "--is-shallow-repository"])
+        .output()
+        .await;
+    matches!(output, Ok(output) if output.status.success() && output.stdout.starts_with(b"true"))
+}
+
+pub async fn last_modified_times(repository: &Path, directory: &str) -> IResult<HashMap<String, DateTime<FixedOffset>>> {
+    if is_shallow(repository).await {
+        warn!("{} is a shallow clone, so last modified times fall back to front matter dates", repository.display());
+        return Ok(HashMap::new());
+    }
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repository)
+        // --relative keeps paths relative to the site when it lives in a subdirectory of the repository.
+        .args(["-c", "core.quotepath=off", "log", "--no-merges", "--format=%x00%cI", "--name-only", "--relative", "--", directory])
+        .output()
+        .await;
+
+    let output = match output {
+        Ok(output) if output.status.success() => output,
+        Ok(output) => {
+            warn!("could not read git history of {}: {}", repository.display(), String::from_utf8_lossy(&output.stderr).trim());
+            return Ok(HashMap::new())
+        }
+        Err(why) => {
+            warn!("could not run git in {}: {why}", repository.display());
+            return Ok(HashMap::new())
+        }
+    };
+
+    let prefix = format!("{}/", directory.trim_matches('/'));
+    let mut times = HashMap::new();
+    let mut current = None;
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        if let Some(date) = line.strip_prefix('\0') {
+            current = DateTime::parse_from_rfc3339(date).ok();
+        } else if let (Some(time), Some(file)) = (current, line.strip_prefix(prefix.as_str())) {
+            times.entry(file.to_string()).or_insert(time);
+        }
+    }
+    if times.is_empty() {
+        warn!("git history of {} has no commits touching {directory}, so last modified times fall back to front matter dates", repository.display());
+    }
+    Ok(times)
+}
@@ -0,0 +1,267 @@
+use std::io::Cursor;
+use std::ops::RangeInclusive;
+use itertools::Itertools;
+use pulldown_cmark::escape::escape_html;
+use syntect::highlighting::{Theme as ColourScheme, ThemeSet};
+use syntect::html::{css_for_theme_with_class_style, line_tokens_to_classed_spans, styled_line_to_highlighted_html, ClassStyle, IncludeBackground};
+use syntect::easy::HighlightLines;
+use syntect::parsing::{ParseState, ScopeStack, SyntaxDefinition, SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
+use tracing::warn;
+use ilgi_core::error::{IlgiError, IResult};
+use crate::config::{Highlight, HighlightStyle};
+
+pub const CLASS_PREFIX: &str = "hl-";
+pub const STYLESHEET: &str = "highlight.scss";
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CodeBlockInfo {
+    pub language: Option<String>,
+    pub highlight: Vec<RangeInclusive<usize>>,
+    pub title: Option<String>,
+    pub line_numbers: Option<bool>,
+    pub diff: bool,
+}
+
+impl CodeBlockInfo {
+    pub fn parse(info: &str) -> CodeBlockInfo {
+        let info = info.trim();
+        let mut block = CodeBlockInfo::default();
+
+        let end = info.find(|c: char| c.is_whitespace() || c == '{').unwrap_or(info.len());
+        let (language, mut rest) = info.split_at(end);
+        if let Some(ranges) = rest.strip_prefix('{') {
+            let (ranges, after) = ranges.split_once('}').unwrap_or((ranges, ""));
+            block.highlight = parse_ranges(ranges);
+            rest = after;
+        }
+        match language.strip_prefix("diff-") {
+            Some(language) => {
+                block.diff = true;
+                block.language = Some(language.to_string());
+            }
+            None if language == "diff" => {
+                block.diff = true;
+                block.language = Some(language.to_string());
+            }
+            None if !language.is_empty() => block.language = Some(language.to_string()),
+            None => {}
+        }
+
+        for attribute in split_attributes(rest) {
+            match attribute.split_once('=') {
+                Some(("title" | "file", value)) => block.title = Some(value.trim_matches('"').to_string()),
+                Some(("hl_lines" | "highlight", value)) => block.highlight.extend(parse_ranges(value.trim_matches('"'))),
+                None if attribute == "linenos" => block.line_numbers = Some(true),
+                None if attribute == "nolinenos" => block.line_numbers = Some(false),
+                None if attribute == "diff" => block.diff = true,
+                _ => warn!("ignoring unknown code block attribute {attribute}"),
+            }
+        }
+        block
+    }
+
+    fn is_highlighted(&self, line: usize) -> bool {
+        self.highlight.iter().any(|range| range.contains(&line))
+    }
+}
+
+fn parse_ranges(ranges: &str) -> Vec<RangeInclusive<usize>> {
+    ranges.split([',', ' '])
+        .filter(|range| !range.is_empty())
+        .filter_map(|range| match range.split_once('-') {
+            Some((start, end)) => Some(start.trim().parse().ok()?..=end.trim().parse().ok()?),
+            None => range.trim().parse().ok().map(|line| line..=line),
+        })
+        .collect()
+}
+
+fn split_attributes(attributes: &str) -> Vec<String> {
+    let mut split = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in attributes.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            c if (c.is_whitespace() || c == ',') && !quoted => {
+                if !current.is_empty() {
+                    split.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        split.push(current);
+    }
+    split
+}
+
+#[derive(Debug)]
+pub struct Highlighter {
+    config: Highlight,
+    syntaxes: SyntaxSet,
+    scheme: ColourScheme,
+}
+
+impl Highlighter {
+    pub fn new(config: &Highlight, grammars: impl IntoIterator<Item = (String, String)>, schemes: impl IntoIterator<Item = (String, String)>) -> IResult<Highlighter> {
+        let invalid = |path: &str, message: String| IlgiError::InvalidHighlightDefinition { path: path.to_string(), message };
+
+        let mut builder = SyntaxSet::load_defaults_newlines().into_builder();
+        for (name, source) in grammars {
+            if !name.ends_with(".sublime-syntax") {
+                warn!("ignoring theme grammar {name}, only .sublime-syntax grammars are supported");
+                continue
+            }
+            builder.add(SyntaxDefinition::load_from_str(&source, true, None).map_err(|why| invalid(&name, why.to_string()))?);
+        }
+
+        let mut themes = ThemeSet::load_defaults();
+        for (name, source) in schemes {
+            let Some(stem) = name.strip_suffix(".tmTheme") else {
+                warn!("ignoring theme colour scheme {name}, only .tmTheme colour schemes are supported");
+                continue
+            };
+            let scheme = ThemeSet::load_from_reader(&mut Cursor::new(source.as_bytes())).map_err(|why| invalid(&name, why.to_string()))?;
+            themes.themes.insert(stem.rsplit('/').next().unwrap_or(stem).to_string(), scheme);
+        }
+
+        let scheme = match themes.themes.remove(&config.theme) {
+            Some(scheme) => scheme,
+            None if !config.enabled => ColourScheme::default(),
+            None => return Err(IlgiError::UnknownHighlightTheme { name: config.theme.clone(), available: themes.themes.keys().join(", ") }.into()),
+        };
+
+        Ok(
+            Highlighter {
+                config: config.clone(),
+                syntaxes: builder.build(),
+                scheme,
+            }
+        )
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    pub fn stylesheet(&self) -> IResult<Option<String>> {
+        if !self.config.enabled || self.config.style != HighlightStyle::Classes {
+            return Ok(None);
+        }
+        css_for_theme_with_class_style(&self.scheme, ClassStyle::SpacedPrefixed { prefix: CLASS_PREFIX })
+            .map(Some)
+            .map_err(|why| IlgiError::InvalidHighlightDefinition { path: self.config.theme.clone(), message: why.to_string() }.into())
+    }
+
+    fn syntax(&self, language: Option<&str>) -> &SyntaxReference {
+        language
+            .and_then(|language| self.syntaxes.find_syntax_by_token(language))
+            .unwrap_or_else(|| {
+                if let Some(language) = language {
+                    warn!("no grammar for code block language {language}, rendering it as plain text");
+                }
+                self.syntaxes.find_syntax_plain_text()
+            })
+    }
+
+    fn classed_line(&self, state: &mut ParseState, stack: &mut ScopeStack, line: &str) -> String {
+        let mut html = String::new();
+        for scope in stack.as_slice() {
+            let classes = scope.build_string().split('.').map(|atom| format!("{CLASS_PREFIX}{atom}")).join(" ");
+            html.push_str(&format!("<span class=\"{classes}\">"));
+        }
+        let spans = state.parse_line(line, &self.syntaxes)
+            .map_err(|why| why.to_string())
+            .and_then(|ops| line_tokens_to_classed_spans(line, &ops, ClassStyle::SpacedPrefixed { prefix: CLASS_PREFIX }, stack).map_err(|why| why.to_string()));
+        match spans {
+            Ok((spans, _)) => html.push_str(&spans),
+            Err(why) => {
+                warn!("could not highlight code line: {why}");
+                let _ = escape_html(&mut html, line);
+            }
+        }
+        html.push_str(&"</span>".repeat(stack.as_slice().len()));
+        html.replace('\n', "")
+    }
+
+    fn inline_line(&self, highlighter: &mut HighlightLines, line: &str) -> String {
+        let html = highlighter.highlight_line(line, &self.syntaxes)
+            .and_then(|regions| styled_line_to_highlighted_html(&regions, IncludeBackground::No));
+        match html {
+            Ok(html) => html.replace('\n', ""),
+            Err(why) => {
+                warn!("could not highlight code line: {why}");
+                let mut html = String::new();
+                let _ = escape_html(&mut html, line.trim_end_matches('\n'));
+                html
+            }
+        }
+    }
+
+    pub fn highlight(&self, info: &CodeBlockInfo, code: &str) -> String {
+        let strip_markers = info.diff && info.language.as_deref() != Some("diff");
+        let syntax = self.syntax(info.language.as_deref());
+        let line_numbers = info.line_numbers.unwrap_or(self.config.line_numbers);
+
+        let mut html = String::from("<figure class=\"code-block\"");
+        if let Some(language) = &info.language {
+            html.push_str(" data-lang=\"");
+            let _ = escape_html(&mut html, language);
+            html.push('"');
+        }
+        html.push('>');
+        if let Some(title) = &info.title {
+            html.push_str("<figcaption>");
+            let _ = escape_html(&mut html, title);
+            html.push_str("</figcaption>");
+        }
+        html.push_str("<pre class=\"highlight\"");
+        if self.config.style == HighlightStyle::Inline {
+            if let Some(background) = self.scheme.settings.background {
+                html.push_str(&format!(" style=\"background-color:#{:02x}{:02x}{:02x};\"", background.r, background.g, background.b));
+            }
+        }
+        html.push_str("><code>");
+
+        let mut state = ParseState::new(syntax);
+        let mut stack = ScopeStack::new();
+        let mut inline = HighlightLines::new(syntax, &self.scheme);
+        for (index, line) in LinesWithEndings::from(code).enumerate() {
+            let number = index + 1;
+            let (marker, class, source) = match line.chars().next() {
+                Some(marker @ ('+' | '-')) if info.diff => (Some(marker), if marker == '+' { " diff-add" } else { " diff-remove" }, line),
+                _ => (None, "", line),
+            };
+            let source = match (marker, strip_markers) {
+                (Some(_), true) => &source[1..],
+                (None, true) if source.starts_with(' ') => &source[1..],
+                _ => source,
+            };
+
+            html.push_str("<span class=\"line");
+            html.push_str(class);
+            if info.is_highlighted(number) {
+                html.push_str(" highlighted");
+            }
+            html.push_str("\">");
+            if line_numbers {
+                html.push_str(&format!("<span class=\"line-number\">{number}</span>"));
+            }
+            if let (Some(marker), true) = (marker, strip_markers) {
+                html.push_str(&format!("<span class=\"diff-marker\">{marker}</span>"));
+            }
+            html.push_str(&match self.config.style {
+                HighlightStyle::Classes => self.classed_line(&mut state, &mut stack, source),
+                HighlightStyle::Inline => self.inline_line(&mut inline, source),
+            });
+            html.push_str("</span>\n");
+        }
+        html.push_str("</code></pre></figure>\n");
+        html
+    }
+}
@@ -0,0 +1,72 @@
+use serde::Serialize;
+use crate::config::IlgiConfig;
+use crate::sitebuild::absolute_url;
+use crate::sitebuild::article::Article;
+use crate::sitebuild::permalink::styled_path;
+use crate::sitebuild::sitemap::escape_xml;
+use crate::sitebuild::Site;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LanguageLink {
+    pub code: String,
+    pub name: String,
+    pub permalink: String,
+    pub current: bool,
+    pub available: bool,
+}
+
+pub fn home_path(config: &IlgiConfig, language: &str) -> String {
+    styled_path(config, &format!("{}/", config.language_prefix(language)))
+}
+
+pub fn home_switcher(config: &IlgiConfig, current: &str) -> Vec<LanguageLink> {
+    config.language_codes()
+        .into_iter()
+        .map(|code| LanguageLink {
+            code: code.to_string(),
+            name: config.language_name(code).to_string(),
+            permalink: absolute_url(config, &home_path(config, code)),
+            current: code == current,
+            available: true,
+        })
+        .collect()
+}
+
+pub fn article_switcher(config: &IlgiConfig, site: &Site, article: &Article) -> Vec<LanguageLink> {
+    let fallback = site.translation(&article.translation_key, &config.default_language);
+    config.language_codes()
+        .into_iter()
+        .map(|code| {
+            let translation = site.translation(&article.translation_key, code);
+            let permalink = match (translation, fallback) {
+                (Some(translation), _) => translation.permalink.clone(),
+                (None, Some(fallback)) => fallback.permalink.clone(),
+                (None, None) => absolute_url(config, &home_path(config, code)),
+            };
+            LanguageLink {
+                code: code.to_string(),
+                name: config.language_name(code).to_string(),
+                permalink,
+                current: code == article.language,
+                available: translation.is_some(),
+            }
+        })
+        .collect()
+}
+
+pub fn hreflang_links(config: &IlgiConfig, links: &[LanguageLink]) -> String {
+    let available = links.iter()
+        .filter(|link| link.available)
+        .collect::<Vec<_>>();
+    if available.len() < 2 {
+        return String::new();
+    }
+
+    let mut tags = available.iter()
+        .map(|link| format!(r#"<link rel="alternate" hreflang="{}" href="{}">"#, escape_xml(&link.code), escape_xml(&link.permalink)))
+        .collect::<String>();
+    if let Some(default) = available.iter().find(|link| link.code == config.default_language) {
+        tags.push_str(&format!(r#"<link rel="alternate" hreflang="x-default" href="{}">"#, escape_xml(&default.permalink)));
+    }
+    tags
+}
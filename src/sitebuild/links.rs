@@ -0,0 +1,183 @@
+use std::path::Path;
+use serde::Serialize;
+use tracing::warn;
+use ilgi_core::error::{IlgiError, IResult};
+use crate::sitebuild::article::Article;
+use crate::sitebuild::markdown::{escape_attribute, escape_text, InternalLink, TocEntry};
+use crate::sitebuild::section::{is_section_index, Section, SECTION_INDEX};
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ArticleLink {
+    pub title: String,
+    pub language: String,
+    pub path: String,
+    pub permalink: String,
+}
+
+fn has_heading(entries: &[TocEntry], id: &str) -> bool {
+    entries.iter().any(|entry| entry.id == id || has_heading(&entry.children, id))
+}
+
+fn is_section_target(target: &str) -> bool {
+    let target = target.trim_start_matches("@/").trim_matches('/');
+    let name = target.rsplit('/').next().unwrap_or_default();
+    is_section_index(Path::new(name)) || name == SECTION_INDEX
+}
+
+fn find_target(articles: &[Article], source: &str, language: &str, target: &str) -> Option<usize> {
+    let target = target.trim_start_matches("@/").trim_matches('/');
+    if let Some(exact) = articles.iter().position(|article| article.source == target) {
+        return Some(exact);
+    }
+
+    let candidates = articles.iter()
+        .enumerate()
+        .filter(|(_, article)| {
+            article.source.strip_suffix(".md") == Some(target) || article.translation_key == target || article.slug == target
+        })
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+    let mut keys = candidates.iter().map(|index| &articles[*index].translation_key).collect::<Vec<_>>();
+    keys.dedup();
+    if keys.len() > 1 {
+        warn!("{source} links to {target}, which matches more than one article");
+    }
+    candidates.iter()
+        .find(|index| articles[**index].language == language)
+        .or_else(|| candidates.first())
+        .copied()
+}
+
+fn find_section<'a>(sections: &'a [Section], language: &str, target: &str) -> Option<&'a Section> {
+    let target = target.trim_start_matches("@/").trim_matches('/');
+    let (directory, name) = target.rsplit_once('/').unwrap_or(("", target));
+    let language = name.strip_suffix(".md").unwrap_or(name)
+        .strip_prefix(&format!("{SECTION_INDEX}."))
+        .unwrap_or(language);
+    let mut candidates = sections.iter().filter(|section| section.directory == directory);
+    let fallback = candidates.clone().next();
+    candidates.find(|section| section.language == language).or(fallback)
+}
+
+fn link_url(link: &InternalLink, source: &str, target: &str, permalink: &str, toc: &[TocEntry], content: &str) -> IResult<String> {
+    match &link.fragment {
+        Some(fragment) if !has_heading(toc, fragment) && !content.contains(&format!("id=\"{}\"", escape_attribute(fragment))) => {
+            Err(IlgiError::BrokenLinkFragment {
+                path: source.to_string(),
+                target: target.to_string(),
+                fragment: fragment.clone(),
+            }.into())
+        }
+        Some(fragment) => Ok(format!("{permalink}#{fragment}")),
+        None => Ok(permalink.to_string()),
+    }
+}
+
+fn link_replacement(link: &InternalLink, url: &str, title: &str) -> String {
+    match link.wiki {
+        true => format!(
+            "<a class=\"wiki-link\" href=\"{}\">{}</a>",
+            escape_attribute(url),
+            escape_text(link.label.as_deref().unwrap_or(title)),
+        ),
+        false => format!("href=\"{}\"", escape_attribute(url)),
+    }
+}
+
+fn section_replacements(sections: &[Section], source: &str, language: &str, links: &[InternalLink]) -> IResult<Vec<(bool, String, String)>> {
+    let mut replacements = vec![];
+    for link in links.iter().filter(|link| is_section_target(&link.target)) {
+        let section = find_section(sections, language, &link.target)
+            .ok_or_else(|| IlgiError::BrokenLink { path: source.to_string(), target: link.target.clone() })?;
+        let target = section.source.clone().unwrap_or_else(|| format!("{}/{SECTION_INDEX}.md", section.directory));
+        let url = link_url(link, source, &target, &section.permalink, &section.toc, &section.content)?;
+        replacements.push((link.wiki, link.marker.clone(), link_replacement(link, &url, &section.title)));
+    }
+    Ok(replacements)
+}
+
+fn replace_markers(html: &mut String, mut replacements: Vec<(bool, String, String)>) {
+    replacements.sort_by_key(|(wiki, _, _)| !wiki);
+    for (_, marker, replacement) in replacements {
+        *html = html.replace(&marker, &replacement);
+    }
+}
+
+fn unpublished_replacement(link: &InternalLink, title: &str) -> String {
+    match link.wiki {
+        true => escape_text(link.label.as_deref().unwrap_or(title)),
+        false => "class=\"unpublished-link\"".to_string(),
+    }
+}
+
+pub fn resolve_links(articles: &mut [Article], listed: &[bool]) -> IResult<()> {
+    let mut resolved = Vec::with_capacity(articles.len());
+    let mut backlinks = vec![vec![]; articles.len()];
+
+    for (index, article) in articles.iter().enumerate() {
+        let mut replacements = vec![];
+        if !listed[index] {
+            resolved.push(replacements);
+            continue;
+        }
+        for link in article.links.iter().filter(|link| !is_section_target(&link.target)) {
+            let target = match link.target.trim_start_matches("@/").trim_matches('/') {
+                "" => Some(index),
+                target => find_target(articles, &article.source, &article.language, target),
+            }
+            .ok_or_else(|| IlgiError::BrokenLink { path: article.source.clone(), target: link.target.clone() })?;
+            let linked = &articles[target];
+            if !listed[target] {
+                warn!("{} links to {}, which is not published, so the link is rendered as plain text", article.source, linked.source);
+                replacements.push((link.wiki, link.marker.clone(), unpublished_replacement(link, &linked.title)));
+                continue;
+            }
+
+            let url = link_url(link, &article.source, &linked.source, &linked.permalink, &linked.toc, &linked.content)?;
+            let url = match (&link.fragment, target == index) {
+                (Some(fragment), true) => format!("#{fragment}"),
+                _ => url,
+            };
+            replacements.push((link.wiki, link.marker.clone(), link_replacement(link, &url, &linked.title)));
+
+            if target != index && !backlinks[target].contains(&index) {
+                backlinks[target].push(index);
+            }
+        }
+        resolved.push(replacements);
+    }
+
+    let sources = articles.iter().map(Article::link).collect::<Vec<_>>();
+    for ((article, replacements), mut linking) in articles.iter_mut().zip(resolved).zip(backlinks) {
+        replace_markers(&mut article.content, replacements);
+        linking.sort_unstable();
+        article.backlinks = linking.into_iter().map(|index| sources[index].clone()).collect();
+    }
+    Ok(())
+}
+
+pub fn resolve_section_links(articles: &mut [Article], sections: &[Section]) -> IResult<()> {
+    for article in articles.iter_mut() {
+        let replacements = section_replacements(sections, &article.source, &article.language, &article.links)?;
+        replace_markers(&mut article.summary, replacements.clone());
+        replace_markers(&mut article.content, replacements);
+    }
+    Ok(())
+}
+
+pub fn resolve_index_links(sections: &mut [Section], articles: &[Article]) -> IResult<()> {
+    for index in 0..sections.len() {
+        let section = &sections[index];
+        let source = section.source.clone().unwrap_or_default();
+        let mut replacements = section_replacements(sections, &source, &section.language, &section.links)?;
+        for link in section.links.iter().filter(|link| !is_section_target(&link.target)) {
+            let linked = find_target(articles, &source, &section.language, &link.target)
+                .map(|target| &articles[target])
+                .ok_or_else(|| IlgiError::BrokenLink { path: source.clone(), target: link.target.clone() })?;
+            let url = link_url(link, &source, &linked.source, &linked.permalink, &linked.toc, &linked.content)?;
+            replacements.push((link.wiki, link.marker.clone(), link_replacement(link, &url, &linked.title)));
+        }
+        replace_markers(&mut sections[index].content, replacements);
+    }
+    Ok(())
+}
@@ -0,0 +1,39 @@
+use chrono::Locale;
+
+const DEFAULT_REGIONS: &[(&str, &str)] = &[
+    ("ar", "ar_SA"),
+    ("cs", "cs_CZ"),
+    ("da", "da_DK"),
+    ("el", "el_GR"),
+    ("en", "en_US"),
+    ("fa", "fa_IR"),
+    ("he", "he_IL"),
+    ("hi", "hi_IN"),
+    ("ja", "ja_JP"),
+    ("ko", "ko_KR"),
+    ("nb", "nb_NO"),
+    ("sv", "sv_SE"),
+    ("uk", "uk_UA"),
+    ("vi", "vi_VN"),
+    ("zh", "zh_CN"),
+];
+
+pub fn locale_name(language: &str) -> String {
+    let language = language.replace('-', "_");
+    if Locale::try_from(language.as_str()).is_ok() {
+        return language;
+    }
+
+    let primary = language.split('_').next().unwrap_or_default().to_lowercase();
+    DEFAULT_REGIONS.iter()
+        .find(|(code, _)| *code == primary)
+        .map(|(_, locale)| locale.to_string())
+        .into_iter()
+        .chain(std::iter::once(format!("{primary}_{}", primary.to_uppercase())))
+        .find(|locale| Locale::try_from(locale.as_str()).is_ok())
+        .unwrap_or_else(|| "POSIX".to_string())
+}
+
+pub fn locale_for(language: &str) -> Locale {
+    Locale::try_from(locale_name(language).as_str()).unwrap_or(Locale::POSIX)
+}
@@ -0,0 +1,714 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use miette::Report;
+use pulldown_cmark::escape::{escape_href, escape_html};
+use pulldown_cmark::{html, CodeBlockKind, CowStr, Event, HeadingLevel, LinkType, Options, Parser, Tag};
+use serde::Serialize;
+use tera::Context;
+use tracing::warn;
+use crate::config::Markdown;
+use crate::sitebuild::highlight::CodeBlockInfo;
+use crate::sitebuild::math::{extract_math, render_math, substitute, Math, MathError};
+use crate::sitebuild::render::render_template_or;
+use crate::sitebuild::slug::slugify;
+use crate::theme::Theme;
+
+pub const TOC_MARKER: &str = "[[toc]]";
+pub const ADMONITION_TEMPLATE: &str = "partials/admonition.html";
+
+const DEFAULT_ADMONITION_TEMPLATE: &str = r#"<div class="admonition admonition-{{ kind }}"><p class="admonition-title">{{ title }}</p>{{ content | safe }}</div>
+"#;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TocEntry {
+    pub level: usize,
+    pub id: String,
+    pub title: String,
+    pub children: Vec<TocEntry>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct InternalLink {
+    pub target: String,
+    pub fragment: Option<String>,
+    pub label: Option<String>,
+    pub wiki: bool,
+    pub marker: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RenderedMarkdown {
+    pub html: String,
+    pub toc: Vec<TocEntry>,
+    pub links: Vec<InternalLink>,
+}
+
+#[derive(Debug)]
+pub enum MarkdownError {
+    Math(MathError),
+    Template(Report),
+}
+
+impl From<MathError> for MarkdownError {
+    fn from(error: MathError) -> Self {
+        MarkdownError::Math(error)
+    }
+}
+
+struct Heading {
+    start: usize,
+    end: usize,
+    level: HeadingLevel,
+    id: String,
+    classes: Vec<String>,
+    title: String,
+}
+
+struct Footnote<'a> {
+    id: String,
+    number: usize,
+    references: usize,
+    events: Vec<Event<'a>>,
+}
+
+struct Renderer<'r, 'a> {
+    theme: &'r Theme,
+    toc_html: String,
+    footnotes: Vec<Footnote<'a>>,
+    links: Vec<InternalLink>,
+}
+
+pub fn markdown_options() -> Options {
+    Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_HEADING_ATTRIBUTES
+}
+
+fn collect_headings(events: &[Event]) -> Vec<Heading> {
+    let mut headings = vec![];
+    let mut used = HashSet::new();
+    let mut current = None;
+
+    for (index, event) in events.iter().enumerate() {
+        match event {
+            Event::Start(Tag::Heading(level, id, classes)) => {
+                current = Some((index, *level, id.map(str::to_string), classes.iter().map(|class| class.to_string()).collect(), String::new()));
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, _, _, _, title)) = &mut current {
+                    title.push_str(text);
+                }
+            }
+            Event::End(Tag::Heading(..)) => {
+                let Some((start, level, id, classes, title)) = current.take() else {
+                    continue
+                };
+                let base = id.unwrap_or_else(|| match slugify(&title) {
+                    slug if slug.is_empty() => "section".to_string(),
+                    slug => slug,
+                });
+                let mut id = base.clone();
+                let mut suffix = 1;
+                while !used.insert(id.clone()) {
+                    id = format!("{base}-{suffix}");
+                    suffix += 1;
+                }
+                headings.push(Heading { start, end: index, level, id, classes, title: title.trim().to_string() });
+            }
+            _ => {}
+        }
+    }
+    headings
+}
+
+fn build_toc(headings: &[Heading]) -> Vec<TocEntry> {
+    fn attach(entries: &mut Vec<TocEntry>, entry: TocEntry) {
+        match entries.last_mut() {
+            Some(parent) if parent.level < entry.level => attach(&mut parent.children, entry),
+            _ => entries.push(entry),
+        }
+    }
+
+    let mut toc = vec![];
+    for heading in headings {
+        attach(&mut toc, TocEntry {
+            level: heading.level as usize,
+            id: heading.id.clone(),
+            title: heading.title.clone(),
+            children: vec![],
+        });
+    }
+    toc
+}
+
+fn render_toc(entries: &[TocEntry], output: &mut String) {
+    if entries.is_empty() {
+        return;
+    }
+    output.push_str("<ul>");
+    for entry in entries {
+        output.push_str("<li><a href=\"#");
+        let _ = escape_html(&mut *output, &entry.id);
+        output.push_str("\">");
+        let _ = escape_html(&mut *output, &entry.title);
+        output.push_str("</a>");
+        render_toc(&entry.children, output);
+        output.push_str("</li>");
+    }
+    output.push_str("</ul>");
+}
+
+fn heading_tags(config: &Markdown, heading: &Heading) -> (String, String) {
+    let mut open = format!("<{}", heading.level);
+    open.push_str(" id=\"");
+    let _ = escape_html(&mut open, &heading.id);
+    open.push('"');
+    if !heading.classes.is_empty() {
+        open.push_str(" class=\"");
+        let _ = escape_html(&mut open, &heading.classes.join(" "));
+        open.push('"');
+    }
+    open.push('>');
+
+    let mut close = String::new();
+    if config.heading_anchors {
+        close.push_str("<a class=\"heading-anchor\" href=\"#");
+        let _ = escape_html(&mut close, &heading.id);
+        close.push_str("\" aria-hidden=\"true\">");
+        let _ = escape_html(&mut close, &config.anchor_text);
+        close.push_str("</a>");
+    }
+    close.push_str(&format!("</{}>\n", heading.level));
+    (open, close)
+}
+
+fn restore_math_titles(entries: &mut [TocEntry], math: &[Math]) {
+    for entry in entries {
+        entry.title = substitute(&entry.title, |index| math.get(index).map(|math| math.latex.clone()));
+        restore_math_titles(&mut entry.children, math);
+    }
+}
+
+fn merge_text<'a>(events: impl Iterator<Item = Event<'a>>) -> Vec<Event<'a>> {
+    let mut merged: Vec<Event<'a>> = vec![];
+    for event in events {
+        if let (Some(Event::Text(previous)), Event::Text(text)) = (merged.last_mut(), &event) {
+            let joined: CowStr<'a> = format!("{previous}{text}").into();
+            *previous = joined;
+            continue
+        }
+        merged.push(event);
+    }
+    merged
+}
+
+fn block_end(events: &[Event], start: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (index, event) in events.iter().enumerate().skip(start) {
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) if depth == 1 => return Some(index),
+            Event::End(_) => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+fn toc_marker_end(events: &[Event], start: usize) -> Option<usize> {
+    if !matches!(events.get(start), Some(Event::Start(Tag::Paragraph))) {
+        return None;
+    }
+    let mut text = String::new();
+    for (index, event) in events.iter().enumerate().skip(start + 1) {
+        match event {
+            Event::Text(fragment) => text.push_str(fragment),
+            Event::End(Tag::Paragraph) => return (text.trim() == TOC_MARKER).then_some(index),
+            _ => return None,
+        }
+    }
+    None
+}
+
+fn code_block_end(events: &[Event], start: usize) -> Option<(CodeBlockInfo, String, usize)> {
+    let Some(Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info)))) = events.get(start) else {
+        return None;
+    };
+    let mut code = String::new();
+    for (index, event) in events.iter().enumerate().skip(start + 1) {
+        match event {
+            Event::Text(text) => code.push_str(text),
+            Event::End(Tag::CodeBlock(_)) => return Some((CodeBlockInfo::parse(info), code, index)),
+            _ => return None,
+        }
+    }
+    None
+}
+
+fn admonition_marker(events: &[Event], start: usize) -> Option<(String, Option<String>, usize)> {
+    if !matches!(events.get(start..start + 2), Some([Event::Start(Tag::BlockQuote), Event::Start(Tag::Paragraph)])) {
+        return None;
+    }
+    let mut text = String::new();
+    let mut index = start + 2;
+    while let Some(Event::Text(fragment)) = events.get(index) {
+        text.push_str(fragment);
+        index += 1;
+    }
+
+    let (kind, title) = text.trim().strip_prefix("[!")?.split_once(']')?;
+    if kind.is_empty() || !kind.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let title = Some(title.trim()).filter(|title| !title.is_empty()).map(str::to_string);
+    Some((kind.to_ascii_lowercase(), title, index))
+}
+
+fn balanced(line: &[Event]) -> bool {
+    let mut depth = 0usize;
+    for event in line {
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => match depth.checked_sub(1) {
+                Some(outer) => depth = outer,
+                None => return false,
+            },
+            _ => {}
+        }
+    }
+    depth == 0
+}
+
+// (is_definition, inline events of the line)
+type DefinitionLine<'a> = (bool, Vec<Event<'a>>);
+
+// PHP Markdown Extra definition lists: one or more term lines followed by one or more `: ` lines,
+// each line a whole run of inline markup.
+fn definition_list<'a>(events: &[Event<'a>], start: usize) -> Option<(Vec<DefinitionLine<'a>>, usize)> {
+    if !matches!(events.get(start), Some(Event::Start(Tag::Paragraph))) {
+        return None;
+    }
+    let end = block_end(events, start)?;
+    let mut lines = vec![vec![]];
+    for event in &events[start + 1..end] {
+        match event {
+            Event::SoftBreak | Event::HardBreak => lines.push(vec![]),
+            event => lines.last_mut()?.push(event.clone()),
+        }
+    }
+
+    let is_definition = |line: &[Event]| matches!(line.first(), Some(Event::Text(text)) if text.starts_with(": "));
+    let terms = lines.iter().take_while(|line| !is_definition(line)).count();
+    if terms == 0
+        || terms == lines.len()
+        || !lines[terms..].iter().all(|line| is_definition(line))
+        || !lines.iter().all(|line| !line.is_empty() && balanced(line)) {
+        return None;
+    }
+
+    let items = lines.into_iter()
+        .map(|mut line| {
+            let definition = is_definition(&line);
+            if let (true, Some(Event::Text(text))) = (definition, line.first_mut()) {
+                *text = CowStr::from(text[2..].to_string());
+            }
+            (definition, line)
+        })
+        .collect();
+    Some((items, end))
+}
+
+fn autolink(text: &str) -> Option<Vec<Event<'static>>> {
+    let mut events = vec![];
+    let mut copied = 0;
+    let mut search = 0;
+    while let Some(found) = ["https://", "http://", "www."].iter().filter_map(|prefix| text[search..].find(prefix)).min() {
+        let start = search + found;
+        let boundary = text[..start].chars().next_back().is_none_or(|c| c.is_whitespace() || "(*_~".contains(c));
+        let end = text[start..].find(|c: char| c.is_whitespace() || c == '<').map_or(text.len(), |end| start + end);
+        let mut url = &text[start..end];
+        loop {
+            let trimmed = url.trim_end_matches(['?', '!', '.', ',', ':', '*', '_', '~', '\'', '"']);
+            let trimmed = match trimmed.strip_suffix(')') {
+                Some(inner) if trimmed.matches(')').count() > trimmed.matches('(').count() => inner,
+                _ => trimmed,
+            };
+            if trimmed.len() == url.len() {
+                break;
+            }
+            url = trimmed;
+        }
+
+        let host = url.trim_start_matches("https://").trim_start_matches("http://").split(['/', '?', '#']).next().unwrap_or_default();
+        if !boundary || !host.contains('.') || host.ends_with('.') {
+            search = start + 1;
+            continue;
+        }
+
+        let href = if url.starts_with("www.") { format!("http://{url}") } else { url.to_string() };
+        if copied < start {
+            events.push(Event::Text(CowStr::from(text[copied..start].to_string())));
+        }
+        events.push(Event::Start(Tag::Link(LinkType::Autolink, CowStr::from(href.clone()), CowStr::from(""))));
+        events.push(Event::Text(CowStr::from(url.to_string())));
+        events.push(Event::End(Tag::Link(LinkType::Autolink, CowStr::from(href), CowStr::from(""))));
+        copied = start + url.len();
+        search = copied;
+    }
+
+    if events.is_empty() {
+        return None;
+    }
+    if copied < text.len() {
+        events.push(Event::Text(CowStr::from(text[copied..].to_string())));
+    }
+    Some(events)
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    chars.next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+fn plain_text(text: &str) -> Vec<Event<'static>> {
+    match autolink(text) {
+        Some(events) => events,
+        None if text.is_empty() => vec![],
+        None => vec![Event::Text(CowStr::from(text.to_string()))],
+    }
+}
+
+fn split_fragment(target: &str) -> (String, Option<String>) {
+    match target.split_once('#') {
+        Some((target, fragment)) => (target.trim().to_string(), Some(fragment.trim().to_string()).filter(|fragment| !fragment.is_empty())),
+        None => (target.trim().to_string(), None),
+    }
+}
+
+pub fn escape_attribute(text: &str) -> String {
+    let mut escaped = String::new();
+    let _ = escape_href(&mut escaped, text);
+    escaped
+}
+
+pub fn escape_text(text: &str) -> String {
+    let mut escaped = String::new();
+    let _ = escape_html(&mut escaped, text);
+    escaped
+}
+
+impl<'r, 'a> Renderer<'r, 'a> {
+    fn footnote(&mut self, label: &str) -> &mut Footnote<'a> {
+        let id = match slugify(label) {
+            id if id.is_empty() => label.to_string(),
+            id => id,
+        };
+        let index = match self.footnotes.iter().position(|footnote| footnote.id == id) {
+            Some(index) => index,
+            None => {
+                self.footnotes.push(Footnote { id, number: 0, references: 0, events: vec![] });
+                self.footnotes.len() - 1
+            }
+        };
+        &mut self.footnotes[index]
+    }
+
+    fn reference(&mut self, label: &str) -> String {
+        let next = self.footnotes.iter().filter(|footnote| footnote.number > 0).count() + 1;
+        let footnote = self.footnote(label);
+        if footnote.number == 0 {
+            footnote.number = next;
+        }
+        footnote.references += 1;
+        format!(
+            "<sup class=\"footnote-reference\" id=\"fnref-{id}-{reference}\"><a href=\"#fn-{id}\">{number}</a></sup>",
+            id = escape_attribute(&footnote.id),
+            reference = footnote.references,
+            number = footnote.number,
+        )
+    }
+
+    fn internal_link(&mut self, destination: &str) {
+        let (target, fragment) = split_fragment(destination);
+        self.links.push(InternalLink {
+            target,
+            fragment,
+            label: None,
+            wiki: false,
+            marker: format!("href=\"{}\"", escape_attribute(destination)),
+        });
+    }
+
+    fn wiki_link(&mut self, inner: &str) -> String {
+        let (target, label) = match inner.split_once('|') {
+            Some((target, label)) => (target, Some(label.trim().to_string()).filter(|label| !label.is_empty())),
+            None => (inner, None),
+        };
+        let (target, fragment) = split_fragment(target.trim().trim_start_matches("@/"));
+        let href = match &fragment {
+            Some(fragment) => format!("@/{target}#{fragment}"),
+            None => format!("@/{target}"),
+        };
+        let html = format!(
+            "<a class=\"wiki-link\" href=\"{}\">{}</a>",
+            escape_attribute(&href),
+            escape_text(label.as_deref().unwrap_or(inner.trim())),
+        );
+        self.links.push(InternalLink { target, fragment, label, wiki: true, marker: html.clone() });
+        html
+    }
+
+    fn link_text(&mut self, text: &str) -> Option<Vec<Event<'static>>> {
+        let mut events = vec![];
+        let mut copied = 0;
+        let mut search = 0;
+        while let Some(found) = text[search..].find("[[") {
+            let start = search + found;
+            let Some(length) = text[start + 2..].find("]]") else {
+                break
+            };
+            let end = start + 2 + length;
+            let inner = &text[start + 2..end];
+            if inner.trim().is_empty() || inner.contains('\n') || text[start..end + 2] == *TOC_MARKER {
+                search = start + 2;
+                continue;
+            }
+            events.extend(plain_text(&text[copied..start]));
+            events.push(Event::Html(CowStr::from(self.wiki_link(inner))));
+            copied = end + 2;
+            search = copied;
+        }
+
+        if events.is_empty() {
+            return autolink(text);
+        }
+        events.extend(plain_text(&text[copied..]));
+        Some(events)
+    }
+
+    fn admonition(&mut self, kind: &str, title: Option<String>, body: Vec<Event<'a>>) -> Result<String, MarkdownError> {
+        let mut content = String::new();
+        html::push_html(&mut content, self.rewrite(body)?.into_iter());
+
+        let mut context = Context::new();
+        context.insert("kind", kind);
+        context.insert("title", &title.unwrap_or_else(|| capitalize(kind)));
+        context.insert("content", &content);
+        render_template_or(self.theme, ADMONITION_TEMPLATE, DEFAULT_ADMONITION_TEMPLATE, &context)
+            .map_err(MarkdownError::Template)
+    }
+
+    fn rewrite(&mut self, mut events: Vec<Event<'a>>) -> Result<Vec<Event<'a>>, MarkdownError> {
+        let mut rewritten = Vec::with_capacity(events.len());
+        let mut link_depth = 0;
+        let mut in_code = false;
+        let mut index = 0;
+        while index < events.len() {
+            if let Some(end) = toc_marker_end(&events, index) {
+                rewritten.push(Event::Html(CowStr::from(self.toc_html.clone())));
+                index = end + 1;
+                continue;
+            }
+            if let Some((info, code, end)) = code_block_end(&events, index).filter(|_| self.theme.highlighter.enabled()) {
+                rewritten.push(Event::Html(CowStr::from(self.theme.highlighter.highlight(&info, &code))));
+                index = end + 1;
+                continue;
+            }
+            if let Some((kind, title, body_start)) = admonition_marker(&events, index) {
+                let end = block_end(&events, index).unwrap_or(events.len());
+                let (open_paragraph, skip) = match events.get(body_start) {
+                    Some(Event::SoftBreak | Event::HardBreak) => (true, 1),
+                    Some(Event::End(Tag::Paragraph)) => (false, 1),
+                    _ => (true, 0),
+                };
+                let mut body = vec![];
+                if open_paragraph {
+                    body.push(Event::Start(Tag::Paragraph));
+                }
+                body.extend(events.drain(body_start + skip..end));
+                rewritten.push(Event::Html(CowStr::from(self.admonition(&kind, title, body)?)));
+                index = body_start + skip + 1;
+                continue;
+            }
+            if let Some((mut items, mut end)) = definition_list(&events, index) {
+                while let Some((more, next)) = definition_list(&events, end + 1) {
+                    items.extend(more);
+                    end = next;
+                }
+                let mut html = String::from("<dl>\n");
+                for (definition, line) in items {
+                    let tag = if definition { "dd" } else { "dt" };
+                    html.push_str(&format!("<{tag}>"));
+                    html::push_html(&mut html, self.rewrite(line)?.into_iter());
+                    html.push_str(&format!("</{tag}>\n"));
+                }
+                html.push_str("</dl>\n");
+                rewritten.push(Event::Html(CowStr::from(html)));
+                index = end + 1;
+                continue;
+            }
+            if let Some(Event::Start(Tag::FootnoteDefinition(label))) = events.get(index) {
+                let label = label.to_string();
+                let end = block_end(&events, index).unwrap_or(events.len());
+                let body = events.drain(index + 1..end).collect();
+                self.footnote(&label).events = self.rewrite(body)?;
+                index += 2;
+                continue;
+            }
+
+            let event = std::mem::replace(&mut events[index], Event::SoftBreak);
+            index += 1;
+            match &event {
+                Event::Start(Tag::Link(_, destination, _)) if destination.starts_with("@/") => {
+                    self.internal_link(destination);
+                    link_depth += 1;
+                }
+                Event::Start(Tag::Link(..) | Tag::Image(..)) => link_depth += 1,
+                Event::End(Tag::Link(..) | Tag::Image(..)) => link_depth -= 1,
+                Event::Start(Tag::CodeBlock(_)) => in_code = true,
+                Event::End(Tag::CodeBlock(_)) => in_code = false,
+                Event::FootnoteReference(label) => {
+                    rewritten.push(Event::Html(CowStr::from(self.reference(label))));
+                    continue;
+                }
+                Event::Text(text) if link_depth == 0 && !in_code => {
+                    if let Some(linked) = self.link_text(text) {
+                        rewritten.extend(linked);
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+            rewritten.push(event);
+        }
+        Ok(rewritten)
+    }
+
+    fn footnotes_html(&mut self) -> String {
+        if self.footnotes.is_empty() {
+            return String::new();
+        }
+        let mut next = self.footnotes.iter().map(|footnote| footnote.number).max().unwrap_or_default();
+        for footnote in self.footnotes.iter_mut().filter(|footnote| footnote.number == 0) {
+            next += 1;
+            footnote.number = next;
+        }
+        self.footnotes.sort_by_key(|footnote| footnote.number);
+
+        let mut html = String::from("<section class=\"footnotes\">\n<ol>\n");
+        for footnote in std::mem::take(&mut self.footnotes) {
+            if footnote.events.is_empty() {
+                warn!("footnote [^{}] is referenced but never defined", footnote.id);
+            }
+            let mut content = String::new();
+            html::push_html(&mut content, footnote.events.into_iter());
+
+            let id = escape_attribute(&footnote.id);
+            let backrefs = (1..=footnote.references)
+                .map(|reference| format!(" <a href=\"#fnref-{id}-{reference}\" class=\"footnote-backref\">↩</a>"))
+                .collect::<String>();
+            let content = match content.trim_end().strip_suffix("</p>") {
+                Some(paragraph) => format!("{}{backrefs}</p>", paragraph.trim_end()),
+                None => format!("{content}{backrefs}"),
+            };
+            html.push_str(&format!("<li id=\"fn-{id}\">{content}</li>\n"));
+        }
+        html.push_str("</ol>\n</section>\n");
+        html
+    }
+}
+
+pub fn render_markdown(config: &Markdown, theme: &Theme, source: &str) -> Result<RenderedMarkdown, MarkdownError> {
+    let (source, math) = match config.math {
+        true => {
+            let (source, math) = extract_math(source);
+            (Cow::Owned(source), math)
+        }
+        false => (Cow::Borrowed(source), vec![]),
+    };
+    let mathml = render_math(&math)?;
+
+    let mut events = merge_text(Parser::new_ext(&source, markdown_options()));
+    let headings = collect_headings(&events);
+    let mut toc = build_toc(&headings);
+    restore_math_titles(&mut toc, &math);
+
+    for heading in &headings {
+        let (open, close) = heading_tags(config, heading);
+        events[heading.start] = Event::Html(CowStr::from(open));
+        events[heading.end] = Event::Html(CowStr::from(close));
+    }
+
+    let mut toc_html = String::from("<nav class=\"toc\">");
+    render_toc(&toc, &mut toc_html);
+    toc_html.push_str("</nav>");
+
+    let mut renderer = Renderer { theme, toc_html, footnotes: vec![], links: vec![] };
+    let rewritten = renderer.rewrite(events)?;
+
+    let mut html = String::with_capacity(source.len() * 3 / 2);
+    html::push_html(&mut html, rewritten.into_iter());
+    html.push_str(&renderer.footnotes_html());
+    if !mathml.is_empty() {
+        html = substitute(&html, |index| mathml.get(index).cloned());
+    }
+    Ok(RenderedMarkdown { html, toc, links: renderer.links })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definitions(source: &str) -> Option<Vec<(bool, String)>> {
+        let events = merge_text(Parser::new_ext(source, markdown_options()));
+        let (items, _) = definition_list(&events, 0)?;
+        Some(items.into_iter()
+            .map(|(definition, line)| {
+                let mut html = String::new();
+                html::push_html(&mut html, line.into_iter());
+                (definition, html)
+            })
+            .collect())
+    }
+
+    #[test]
+    fn reads_terms_followed_by_definitions() {
+        assert_eq!(definitions("Apple\n: A fruit\n: A company"), Some(vec![
+            (false, "Apple".to_string()),
+            (true, "A fruit".to_string()),
+            (true, "A company".to_string()),
+        ]));
+        assert_eq!(definitions("Apple\nPear\n: Fruit"), Some(vec![
+            (false, "Apple".to_string()),
+            (false, "Pear".to_string()),
+            (true, "Fruit".to_string()),
+        ]));
+    }
+
+    #[test]
+    fn keeps_inline_markup_on_its_line() {
+        assert_eq!(definitions("*Term*\n: uses `code` and [a link](/x)"), Some(vec![
+            (false, "<em>Term</em>".to_string()),
+            (true, "uses <code>code</code> and <a href=\"/x\">a link</a>".to_string()),
+        ]));
+    }
+
+    #[test]
+    fn leaves_prose_alone() {
+        assert_eq!(definitions("Just one line"), None);
+        assert_eq!(definitions("Term: inline colon"), None);
+        assert_eq!(definitions("Some wrapped prose\nNote: the colon is mid-line"), None);
+        assert_eq!(definitions(": a definition\nwithout a term"), None);
+        assert_eq!(definitions("A wrapped line\n: that looks like a definition\nand then carries on"), None);
+    }
+
+    #[test]
+    fn leaves_markup_across_lines_alone() {
+        assert_eq!(definitions("*emphasis that\n: wraps*"), None);
+        assert_eq!(definitions("[a link that\n: wraps](/x)"), None);
+    }
+}
@@ -0,0 +1,295 @@
+use std::ops::Range;
+use latex2mathml::{latex_to_mathml, DisplayStyle};
+use pulldown_cmark::{CodeBlockKind, Event, Parser, Tag};
+
+// Math is swapped for characters from Supplementary Private Use Area-A while Markdown is rendered.
+const PLACEHOLDER_BASE: u32 = 0xF0000;
+const PLACEHOLDER_LAST: u32 = 0xFFFFD;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Math {
+    pub latex: String,
+    pub display: bool,
+    pub offset: usize,
+    pub len: usize,
+    // A private use character from the source, kept as is.
+    pub literal: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MathError {
+    pub offset: usize,
+    pub len: usize,
+    pub message: String,
+}
+
+fn placeholder(index: usize) -> char {
+    u32::try_from(index).ok()
+        .and_then(|index| PLACEHOLDER_BASE.checked_add(index))
+        .filter(|c| *c <= PLACEHOLDER_LAST)
+        .and_then(char::from_u32)
+        .unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+fn placeholder_index(c: char) -> Option<usize> {
+    (PLACEHOLDER_BASE..=PLACEHOLDER_LAST).contains(&(c as u32)).then(|| (c as u32 - PLACEHOLDER_BASE) as usize)
+}
+
+fn fence_open(line: &str) -> Option<(char, usize, &str)> {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let marker = trimmed.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let count = trimmed.chars().take_while(|c| *c == marker).count();
+    (count >= 3).then(|| (marker, count, trimmed[count..].trim()))
+}
+
+fn is_fence_close(line: &str, marker: char, count: usize) -> bool {
+    let trimmed = line.trim_start_matches(' ');
+    line.len() - trimmed.len() <= 3
+        && trimmed.chars().take_while(|c| *c == marker).count() >= count
+        && trimmed.trim_start_matches(marker).trim().is_empty()
+}
+
+fn inline_end(source: &str, start: usize) -> Option<usize> {
+    let bytes = source.as_bytes();
+    if bytes.get(start).is_none_or(u8::is_ascii_whitespace) {
+        return None;
+    }
+    let mut index = start;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'\\' => index += 2,
+            b'`' => return None,
+            b'\n' if bytes.get(index + 1) == Some(&b'\n') => return None,
+            b'$' if !bytes[index - 1].is_ascii_whitespace() && !bytes.get(index + 1).is_some_and(u8::is_ascii_digit) => return Some(index),
+            _ => index += 1,
+        }
+    }
+    None
+}
+
+// Display math may wrap over lines but ends with its paragraph, so a stray $$ cannot swallow the rest of the page.
+fn display_end(source: &str, start: usize) -> Option<usize> {
+    let mut line = start;
+    while line < source.len() {
+        let next = source[line..].find('\n').map_or(source.len(), |end| line + end + 1);
+        let text = &source[line..next];
+        if line > start && (text.trim().is_empty() || fence_open(text).is_some()) {
+            return None;
+        }
+        if let Some(found) = text.find("$$") {
+            return Some(line + found);
+        }
+        line = next;
+    }
+    None
+}
+
+// Private use characters already in the source become placeholders for themselves, so they are
+// never mistaken for math.
+fn push_source(output: &mut String, math: &mut Vec<Math>, source: &str, offset: usize) {
+    for (index, c) in source.char_indices() {
+        match placeholder_index(c) {
+            Some(_) => {
+                output.push(placeholder(math.len()));
+                math.push(Math { latex: c.to_string(), display: false, offset: offset + index, len: c.len_utf8(), literal: true });
+            }
+            None => output.push(c),
+        }
+    }
+}
+
+fn indented_code(source: &str) -> Vec<Range<usize>> {
+    Parser::new(source).into_offset_iter()
+        .filter_map(|(event, range)| matches!(event, Event::Start(Tag::CodeBlock(CodeBlockKind::Indented))).then_some(range))
+        .collect()
+}
+
+pub fn extract_math(source: &str) -> (String, Vec<Math>) {
+    let bytes = source.as_bytes();
+    let indented = indented_code(source);
+    let mut output = String::with_capacity(source.len());
+    let mut math = vec![];
+    let mut copied = 0;
+    let mut index = 0;
+    let mut line_start = true;
+
+    let mut replace = |output: &mut String, math: &mut Vec<Math>, start: usize, end: usize, latex: &str, display: bool, suffix: &str| {
+        push_source(output, math, &source[copied..start], copied);
+        output.push(placeholder(math.len()));
+        output.push_str(suffix);
+        math.push(Math { latex: latex.trim().to_string(), display, offset: start, len: end - start, literal: false });
+        copied = end;
+    };
+
+    while index < bytes.len() {
+        if line_start {
+            line_start = false;
+            let line_end = source[index..].find('\n').map_or(source.len(), |end| index + end + 1);
+            if let Some(code) = indented.iter().find(|code| code.start < line_end && code.end > index) {
+                let last = code.end - 1;
+                index = source[last..].find('\n').map_or(source.len(), |end| last + end + 1);
+                line_start = true;
+                continue;
+            }
+            if let Some((marker, count, info)) = fence_open(&source[index..line_end]) {
+                let mut close = line_end;
+                let mut close_end = source.len();
+                while close < source.len() {
+                    let next = source[close..].find('\n').map_or(source.len(), |end| close + end + 1);
+                    if is_fence_close(&source[close..next], marker, count) {
+                        close_end = next;
+                        break;
+                    }
+                    close = next;
+                }
+                if info == "math" {
+                    let content = &source[line_end..close.min(source.len())];
+                    replace(&mut output, &mut math, index, close_end, content, true, "\n");
+                }
+                index = close_end;
+                line_start = true;
+                continue;
+            }
+        }
+
+        match bytes[index] {
+            b'\\' if bytes.get(index + 1) != Some(&b'\n') => index += 2,
+            b'\n' => {
+                index += 1;
+                line_start = true;
+            }
+            b'`' => {
+                let run = bytes[index..].iter().take_while(|c| **c == b'`').count();
+                let fence = "`".repeat(run);
+                let mut search = index + run;
+                index = loop {
+                    match source[search..].find(&fence) {
+                        Some(found) if bytes.get(search + found + run) == Some(&b'`') => {
+                            search += found + bytes[search + found..].iter().take_while(|c| **c == b'`').count();
+                        }
+                        Some(found) => break search + found + run,
+                        None => break index + run,
+                    }
+                };
+            }
+            b'$' if bytes.get(index + 1) == Some(&b'$') => {
+                match display_end(source, index + 2) {
+                    Some(end) => {
+                        replace(&mut output, &mut math, index, end + 2, &source[index + 2..end], true, "");
+                        index = end + 2;
+                    }
+                    None => index += 2,
+                }
+            }
+            b'$' => match inline_end(source, index + 1) {
+                Some(end) => {
+                    replace(&mut output, &mut math, index, end + 1, &source[index + 1..end], false, "");
+                    index = end + 1;
+                }
+                None => index += 1,
+            },
+            _ => index += 1,
+        }
+    }
+    let copied = copied.min(source.len());
+    push_source(&mut output, &mut math, &source[copied..], copied);
+    (output, math)
+}
+
+pub fn render_math(math: &[Math]) -> Result<Vec<String>, MathError> {
+    math.iter()
+        .map(|math| {
+            if math.literal {
+                return Ok(math.latex.clone());
+            }
+            let style = if math.display { DisplayStyle::Block } else { DisplayStyle::Inline };
+            latex_to_mathml(&math.latex, style).map_err(|why| MathError {
+                offset: math.offset,
+                len: math.len,
+                message: why.to_string(),
+            })
+        })
+        .collect()
+}
+
+pub fn substitute(text: &str, replacement: impl Fn(usize) -> Option<String>) -> String {
+    let mut output = String::with_capacity(text.len());
+    for c in text.chars() {
+        match placeholder_index(c).and_then(&replacement) {
+            Some(replaced) => output.push_str(&replaced),
+            None => output.push(c),
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn latex(source: &str) -> Vec<(String, bool)> {
+        extract_math(source).1.into_iter()
+            .filter(|math| !math.literal)
+            .map(|math| (math.latex, math.display))
+            .collect()
+    }
+
+    fn restored(source: &str) -> String {
+        let (output, math) = extract_math(source);
+        substitute(&output, |index| math.get(index).map(|math| math.latex.clone()))
+    }
+
+    #[test]
+    fn extracts_inline_and_display_math() {
+        assert_eq!(latex("where $x^2$ grows\n\n$$\n\\sum_i i\n$$\n"), vec![
+            ("x^2".to_string(), false),
+            ("\\sum_i i".to_string(), true),
+        ]);
+        assert_eq!(latex("```math\na + b\n```\n"), vec![("a + b".to_string(), true)]);
+    }
+
+    #[test]
+    fn leaves_prices_alone() {
+        assert!(latex("it costs $5 and $10 now").is_empty());
+    }
+
+    #[test]
+    fn stray_display_marker_stops_at_the_paragraph() {
+        let source = "costs $$5\n\n[a link](https://example.com/?q=$)\n\n<div>html</div>\n\n$$y$$\n";
+        assert_eq!(latex(source), vec![("y".to_string(), true)]);
+        assert!(extract_math(source).0.contains("[a link](https://example.com/?q=$)"));
+    }
+
+    #[test]
+    fn display_math_wraps_within_a_paragraph() {
+        assert_eq!(latex("so $$a +\nb$$ holds"), vec![("a +\nb".to_string(), true)]);
+    }
+
+    #[test]
+    fn stray_display_marker_stops_at_a_fence() {
+        assert!(latex("costs $$5\n```\n$$\n```\n").is_empty());
+    }
+
+    #[test]
+    fn skips_code() {
+        assert!(latex("`$x$` and\n\n    $y$\n\n```\n$$z$$\n```\n").is_empty());
+    }
+
+    #[test]
+    fn keeps_private_use_characters() {
+        let source = "icon \u{F0000}\u{F0001} then $x$ and \u{FFFFD}";
+        assert_eq!(latex(source), vec![("x".to_string(), false)]);
+        assert_eq!(restored(source), "icon \u{F0000}\u{F0001} then x and \u{FFFFD}");
+    }
+
+    #[test]
+    fn placeholders_stay_in_the_private_use_area() {
+        assert_eq!(placeholder_index(placeholder(3)), Some(3));
+        assert_eq!(placeholder_index('\u{FFFFE}'), None);
+        assert_eq!(placeholder_index('\u{100000}'), None);
+        assert_eq!(placeholder(0x10000), char::REPLACEMENT_CHARACTER);
+    }
+}
@@ -1,8 +1,276 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use chrono::{DateTime, FixedOffset, Utc};
+use tracing::{instrument, warn};
 use ilgi_core::error::IResult;
 use crate::config::IlgiConfig;
+use crate::file_ops::write_output;
+use crate::sitebuild::archive::{collect_archive, write_archive, Archive};
+use crate::sitebuild::article::{Article, load_articles};
+use crate::sitebuild::feed::write_feeds;
+use crate::sitebuild::language::home_path;
+use crate::sitebuild::links::{resolve_index_links, resolve_section_links};
+use crate::sitebuild::pagination::{paginated_urls, write_index};
+use crate::sitebuild::permalink::check_duplicate_urls;
+use crate::sitebuild::redirect::{collect_redirects, write_redirects, Redirect};
+use crate::sitebuild::related::collect_related;
+use crate::sitebuild::search::write_search_indexes;
+use crate::sitebuild::render::write_articles;
+use crate::sitebuild::robots::render_robots;
+use crate::sitebuild::section::{collect_sections, write_sections, Section};
+use crate::sitebuild::series::{collect_series, write_series, Series};
+use crate::sitebuild::sitemap::{render_sitemaps, SitemapEntry};
+use crate::sitebuild::taxonomy::{collect_taxonomies, write_taxonomies, Taxonomy};
+use crate::theme::Theme;
 
 mod git;
+pub mod archive;
+pub mod article;
+pub mod feed;
+pub mod highlight;
+pub mod language;
+pub mod links;
+pub mod locale;
+pub mod markdown;
+pub mod math;
+pub mod pagination;
+pub mod permalink;
+pub mod redirect;
+pub mod related;
+pub mod render;
+pub mod robots;
+pub mod search;
+pub mod section;
+pub mod series;
+pub mod sitemap;
+pub mod slug;
+pub mod summary;
+pub mod taxonomy;
 
-pub async fn build_site(config: &IlgiConfig) -> IResult<()> {
-    
-}
\ No newline at end of file
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Site {
+    pub articles: Vec<Article>,
+    pub commit_times: HashMap<String, DateTime<FixedOffset>>,
+    pub commit: Option<String>,
+    pub redirects: Vec<Redirect>,
+    pub sections: Vec<Section>,
+    pub series: Vec<Series>,
+    pub unlisted: Vec<Article>,
+    pub next_change: Option<DateTime<Utc>>,
+    translation_index: HashMap<String, BTreeMap<String, usize>>,
+}
+
+impl Site {
+    pub fn new(articles: Vec<Article>, commit_times: HashMap<String, DateTime<FixedOffset>>) -> Site {
+        let mut translation_index = HashMap::<String, BTreeMap<String, usize>>::new();
+        for (index, article) in articles.iter().enumerate() {
+            let languages = translation_index.entry(article.translation_key.clone()).or_default();
+            if let Some(existing) = languages.insert(article.language.clone(), index) {
+                warn!("{} and {} are both the \"{}\" translation of \"{}\"", articles[existing].source, article.source, article.language, article.translation_key);
+            }
+        }
+
+        Site {
+            articles,
+            commit_times,
+            commit: None,
+            redirects: vec![],
+            sections: vec![],
+            series: vec![],
+            unlisted: vec![],
+            next_change: None,
+            translation_index,
+        }
+    }
+
+    pub fn last_modified(&self, article: &Article) -> Option<DateTime<FixedOffset>> {
+        article.updated
+            .or_else(|| self.commit_times.get(&article.source).copied())
+            .or(article.date)
+    }
+
+    pub fn translation(&self, translation_key: &str, language: &str) -> Option<&Article> {
+        self.translation_index.get(translation_key)
+            .and_then(|languages| languages.get(language))
+            .map(|index| &self.articles[*index])
+    }
+
+    pub fn translations<'a>(&'a self, article: &'a Article) -> impl Iterator<Item = &'a Article> + 'a {
+        self.translation_index.get(&article.translation_key)
+            .into_iter()
+            .flat_map(|languages| languages.iter())
+            .filter(move |(language, _)| **language != article.language)
+            .map(|(_, index)| &self.articles[*index])
+    }
+
+    pub fn articles_for(&self, config: &IlgiConfig, language: &str) -> Vec<&Article> {
+        self.articles.iter()
+            .filter(|article| {
+                article.language == language
+                    || (article.language == config.default_language
+                        && self.translation(&article.translation_key, language).is_none())
+            })
+            .collect()
+    }
+
+    pub fn sitemap_entries(&self, config: &IlgiConfig) -> Vec<SitemapEntry> {
+        let homes = config.language_codes()
+            .into_iter()
+            .map(|language| (language.to_string(), absolute_url(config, &home_path(config, language))))
+            .collect::<Vec<_>>();
+        let mut entries = homes.iter()
+            .map(|(language, home)| SitemapEntry {
+                loc: home.clone(),
+                lastmod: self.articles_for(config, language).into_iter().filter_map(|article| self.last_modified(article)).max(),
+                alternates: if homes.len() > 1 { homes.clone() } else { vec![] },
+            })
+            .collect::<Vec<_>>();
+
+        entries.extend(self.articles.iter().map(|article| {
+            let mut alternates = self.translations(article)
+                .map(|translation| (translation.language.clone(), translation.permalink.clone()))
+                .collect::<Vec<_>>();
+            if !alternates.is_empty() {
+                alternates.push((article.language.clone(), article.permalink.clone()));
+                alternates.sort();
+            }
+            SitemapEntry {
+                loc: article.permalink.clone(),
+                lastmod: self.last_modified(article),
+                alternates,
+            }
+        }));
+        entries
+    }
+}
+
+fn page_urls(config: &IlgiConfig, site: &Site, taxonomies: &[Taxonomy], archives: &[Archive]) -> Vec<(String, String)> {
+    let mut pages = site.articles.iter()
+        .chain(&site.unlisted)
+        .map(|article| (article.path.clone(), article.source.clone()))
+        .collect::<Vec<_>>();
+    for language in config.language_codes() {
+        pages.extend(paginated_urls(config, &home_path(config, language), site.articles_for(config, language).len(), None, &format!("the {language} home page")));
+    }
+    for section in site.sections.iter().filter(|section| !section.is_root()) {
+        let name = section.source.clone().unwrap_or_else(|| format!("the {} section ({})", section.directory, section.language));
+        pages.extend(paginated_urls(config, &section.path, section.pages.len(), Some(section.paginate_by.unwrap_or(0)), &name));
+    }
+    pages.extend(site.series.iter().map(|series| (series.path.clone(), format!("the \"{}\" series ({})", series.name, series.language))));
+    for taxonomy in taxonomies {
+        pages.push((taxonomy.path.clone(), format!("the {} taxonomy ({})", taxonomy.name, taxonomy.language)));
+        for term in &taxonomy.terms {
+            let name = format!("the {} term \"{}\" ({})", taxonomy.name, term.name, taxonomy.language);
+            pages.extend(paginated_urls(config, &term.path, term.articles.len(), taxonomy.config.paginate_by, &name));
+        }
+    }
+    let per_page = config.build.archive.paginate_by;
+    for archive in archives {
+        pages.extend(paginated_urls(config, &archive.path, archive.articles.len(), per_page, &format!("the archive ({})", archive.language)));
+        for year in &archive.years {
+            pages.extend(paginated_urls(config, &year.path, year.articles.len(), per_page, &format!("the {} archive ({})", year.year, archive.language)));
+            for month in &year.months {
+                let name = format!("the {}-{:02} archive ({})", month.year, month.month, archive.language);
+                pages.extend(paginated_urls(config, &month.path, month.articles.len(), per_page, &name));
+            }
+        }
+    }
+    pages
+}
+
+#[instrument(skip(config, theme))]
+pub async fn build_site(config: &IlgiConfig, theme: &Theme, source: &Path, output: &Path) -> IResult<Site> {
+    let content_dir = source.join(&config.build.content_dir);
+    let (articles, next_change) = load_articles(config, theme, &content_dir, Utc::now())?;
+    let (mut articles, mut unlisted): (Vec<_>, Vec<_>) = articles.into_iter()
+        .partition(|article| config.build.drafts || !article.expired);
+    let mut sections = collect_sections(config, theme, &content_dir, &mut articles)?;
+    resolve_section_links(&mut articles, &sections)?;
+    resolve_section_links(&mut unlisted, &sections)?;
+    resolve_index_links(&mut sections, &articles)?;
+    let series = collect_series(config, &mut articles);
+    collect_related(config, &mut articles);
+    let commit_times = git::last_modified_times(source, &config.build.content_dir).await?;
+    let mut site = Site::new(articles, commit_times);
+    site.sections = sections;
+    site.series = series;
+    site.unlisted = unlisted;
+    site.next_change = next_change;
+    site.commit = git::head_commit(source).await;
+    site.redirects = collect_redirects(config, &site.articles.iter().chain(&site.unlisted).collect::<Vec<_>>())?;
+
+    let mut taxonomies = vec![];
+    let mut archives = vec![];
+    for language in config.language_codes() {
+        let listed = site.articles_for(config, language);
+        taxonomies.extend(collect_taxonomies(config, language, &listed)?);
+        if config.build.archive.enabled {
+            archives.push(collect_archive(config, language, &listed)?);
+        }
+    }
+    check_duplicate_urls(page_urls(config, &site, &taxonomies, &archives))?;
+
+    write_articles(config, theme, &site, output).await?;
+    write_sections(config, theme, &site, output).await?;
+    write_series(config, theme, &site, output).await?;
+    for language in config.language_codes() {
+        let native = site.articles.iter().filter(|article| article.language == language);
+        write_index(config, theme, &site, output, language).await?;
+        write_feeds(config, output, &home_path(config, language), language, native).await?;
+    }
+    write_taxonomies(config, theme, output, &taxonomies).await?;
+    for archive in &archives {
+        write_archive(config, theme, output, archive).await?;
+    }
+
+    if config.build.search.enabled {
+        write_search_indexes(config, output, &site.articles).await?;
+    }
+
+    if config.build.sitemap.enabled {
+        let mut entries = site.sitemap_entries(config);
+        entries.extend(section::sitemap_entries(&site));
+        entries.extend(series::sitemap_entries(&site));
+        entries.extend(taxonomies.iter().flat_map(|taxonomy| taxonomy.sitemap_entries(&site)));
+        entries.extend(archives.iter().flat_map(|archive| archive::sitemap_entries(&site, archive)));
+        for (name, sitemap) in render_sitemaps(config, &entries) {
+            write_output(output, name, sitemap).await?;
+        }
+    }
+    if config.build.robots.enabled {
+        write_output(output, robots::FILE_NAME, render_robots(config)).await?;
+    }
+    write_redirects(config, theme, output, &site.redirects).await?;
+
+    Ok(site)
+}
+
+pub fn absolute_url(config: &IlgiConfig, path: &str) -> String {
+    if path.contains("://") {
+        return path.to_string();
+    }
+    format!("{}/{}", config.base_url.trim_end_matches('/'), path.trim_start_matches('/'))
+}
+
+pub fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = (bytes[index] == b'%')
+            .then(|| text.get(index + 1..index + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
@@ -0,0 +1,205 @@
+use std::path::Path;
+use miette::WrapErr;
+use serde::Serialize;
+use ilgi_core::error::IResult;
+use crate::config::IlgiConfig;
+use crate::file_ops::write_output;
+use crate::sitebuild::absolute_url;
+use crate::sitebuild::article::Article;
+use crate::sitebuild::language::{home_path, home_switcher, hreflang_links};
+use crate::sitebuild::permalink::{directory_path, styled_path};
+use crate::sitebuild::render::{base_context, output_file, render_template};
+use crate::sitebuild::sitemap::escape_xml;
+use crate::sitebuild::Site;
+use crate::theme::Theme;
+
+pub const INDEX_TEMPLATE: &str = "index.html";
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PageLink {
+    pub number: usize,
+    pub path: String,
+    pub permalink: String,
+    pub current: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Paginator<'a> {
+    pub current: usize,
+    pub total: usize,
+    pub per_page: usize,
+    pub total_items: usize,
+    pub path: String,
+    pub permalink: String,
+    pub first: String,
+    pub last: String,
+    pub previous: Option<String>,
+    pub next: Option<String>,
+    pub numbers: Vec<PageLink>,
+    pub rel_links: String,
+    pub pages: &'a [&'a Article],
+}
+
+pub fn page_path(config: &IlgiConfig, base_path: &str, page: usize) -> String {
+    if page <= 1 {
+        styled_path(config, base_path)
+    } else {
+        let pattern = config.build.pagination.path.trim_start_matches('/');
+        styled_path(config, &format!("{}{}", directory_path(base_path), pattern.replace("{page}", &page.to_string())))
+    }
+}
+
+fn page_count(config: &IlgiConfig, items: usize, per_page: Option<usize>) -> (usize, usize) {
+    let per_page = match per_page.unwrap_or(config.build.pagination.paginate_by) {
+        0 => items.max(1),
+        per_page => per_page,
+    };
+    (per_page, items.div_ceil(per_page).max(1))
+}
+
+pub fn paginated_urls(config: &IlgiConfig, base_path: &str, items: usize, per_page: Option<usize>, name: &str) -> Vec<(String, String)> {
+    let (_, total) = page_count(config, items, per_page);
+    (1..=total)
+        .map(|page| match page {
+            1 => (page_path(config, base_path, page), name.to_string()),
+            page => (page_path(config, base_path, page), format!("page {page} of {name}")),
+        })
+        .collect()
+}
+
+pub fn paginate<'a>(config: &IlgiConfig, base_path: &str, articles: &'a [&'a Article], per_page: Option<usize>) -> Vec<Paginator<'a>> {
+    let (per_page, total) = page_count(config, articles.len(), per_page);
+    let link = |page: usize| absolute_url(config, &page_path(config, base_path, page));
+
+    (1..=total)
+        .map(|current| {
+            let start = (current - 1) * per_page;
+            let end = (start + per_page).min(articles.len());
+            let previous = (current > 1).then(|| link(current - 1));
+            let next = (current < total).then(|| link(current + 1));
+
+            let mut rel_links = String::new();
+            if let Some(previous) = &previous {
+                rel_links.push_str(&format!(r#"<link rel="prev" href="{}">"#, escape_xml(previous)));
+            }
+            if let Some(next) = &next {
+                rel_links.push_str(&format!(r#"<link rel="next" href="{}">"#, escape_xml(next)));
+            }
+
+            Paginator {
+                current,
+                total,
+                per_page,
+                total_items: articles.len(),
+                path: page_path(config, base_path, current),
+                permalink: link(current),
+                first: link(1),
+                last: link(total),
+                previous,
+                next,
+                numbers: (1..=total)
+                    .map(|number| PageLink {
+                        number,
+                        path: page_path(config, base_path, number),
+                        permalink: link(number),
+                        current: number == current,
+                    })
+                    .collect(),
+                rel_links,
+                pages: &articles[start..end],
+            }
+        })
+        .collect()
+}
+
+pub async fn write_index(config: &IlgiConfig, theme: &Theme, site: &Site, output: &Path, language: &str) -> IResult<()> {
+    let articles = site.articles_for(config, language);
+    let switcher = home_switcher(config, language);
+    let root = site.sections.iter().find(|section| section.is_root() && section.language == language);
+    let template = root.and_then(|section| section.template.as_deref()).unwrap_or(INDEX_TEMPLATE);
+    for paginator in paginate(config, &home_path(config, language), &articles, None) {
+        let mut context = base_context(config, language);
+        if let Some(root) = root {
+            context.insert("section", &root.context(site));
+        }
+        context.insert("paginator", &paginator);
+        context.insert("hreflang", &hreflang_links(config, &switcher));
+        context.insert("language_switcher", &switcher);
+        let html = render_template(theme, template, &context)
+            .wrap_err_with(|| format!("while rendering index page {}", paginator.current))?;
+        write_output(output, output_file(&paginator.path), html).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::test_config;
+    use super::*;
+
+    fn articles(count: usize) -> Vec<Article> {
+        (1..=count)
+            .map(|number| Article { source: format!("{number}.md"), ..Default::default() })
+            .collect()
+    }
+
+    fn page_sizes(paginators: &[Paginator]) -> Vec<usize> {
+        paginators.iter().map(|paginator| paginator.pages.len()).collect()
+    }
+
+    #[test]
+    fn no_articles_still_make_one_page() {
+        let paginators = paginate(&test_config(), "/", &[], Some(2));
+        assert_eq!(page_sizes(&paginators), [0]);
+        assert_eq!((paginators[0].current, paginators[0].total), (1, 1));
+        assert_eq!((paginators[0].previous.as_deref(), paginators[0].next.as_deref()), (None, None));
+        assert_eq!(paginators[0].rel_links, "");
+    }
+
+    #[test]
+    fn exact_multiples_fill_every_page() {
+        let articles = articles(4);
+        let articles = articles.iter().collect::<Vec<_>>();
+        let paginators = paginate(&test_config(), "/", &articles, Some(2));
+        assert_eq!(page_sizes(&paginators), [2, 2]);
+        assert_eq!(paginators[1].pages[0].source, "3.md");
+    }
+
+    #[test]
+    fn remainders_go_on_the_last_page() {
+        let articles = articles(5);
+        let articles = articles.iter().collect::<Vec<_>>();
+        let paginators = paginate(&test_config(), "/tags/rust/", &articles, Some(2));
+        assert_eq!(page_sizes(&paginators), [2, 2, 1]);
+        assert_eq!(paginators.iter().map(|paginator| paginator.path.as_str()).collect::<Vec<_>>(), ["/tags/rust/", "/tags/rust/page/2/", "/tags/rust/page/3/"]);
+
+        let middle = &paginators[1];
+        assert_eq!(middle.previous.as_deref(), Some("https://example.com/tags/rust/"));
+        assert_eq!(middle.next.as_deref(), Some("https://example.com/tags/rust/page/3/"));
+        assert_eq!(middle.rel_links, r#"<link rel="prev" href="https://example.com/tags/rust/"><link rel="next" href="https://example.com/tags/rust/page/3/">"#);
+        assert_eq!(middle.numbers.iter().map(|number| number.current).collect::<Vec<_>>(), [false, true, false]);
+        assert_eq!((middle.first.as_str(), middle.last.as_str()), ("https://example.com/tags/rust/", "https://example.com/tags/rust/page/3/"));
+    }
+
+    #[test]
+    fn paginate_by_falls_back_to_the_config_and_zero_means_one_page() {
+        let articles = articles(3);
+        let articles = articles.iter().collect::<Vec<_>>();
+        let mut config = test_config();
+        config.build.pagination.paginate_by = 2;
+        assert_eq!(page_sizes(&paginate(&config, "/", &articles, None)), [2, 1]);
+        assert_eq!(page_sizes(&paginate(&config, "/", &articles, Some(0))), [3]);
+    }
+
+    #[test]
+    fn lists_the_urls_of_every_page() {
+        let mut config = test_config();
+        config.build.pagination.path = "p{page}/".to_string();
+        assert_eq!(paginated_urls(&config, "/archive/", 3, Some(1), "the archive"), [
+            ("/archive/".to_string(), "the archive".to_string()),
+            ("/archive/p2/".to_string(), "page 2 of the archive".to_string()),
+            ("/archive/p3/".to_string(), "page 3 of the archive".to_string()),
+        ]);
+        assert_eq!(paginated_urls(&config, "/archive/", 0, Some(1), "the archive").len(), 1);
+    }
+}
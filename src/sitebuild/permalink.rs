@@ -0,0 +1,175 @@
+use std::collections::BTreeMap;
+use chrono::Datelike;
+use chrono_tz::Tz;
+use itertools::Itertools;
+use ilgi_core::error::{IlgiError, IResult};
+use crate::config::{IlgiConfig, UrlStyle};
+use crate::sitebuild::article::Article;
+use crate::sitebuild::slug::url_slug;
+
+fn section_pattern<'a>(config: &'a IlgiConfig, parent: &str) -> &'a str {
+    config.build.permalinks.sections.iter()
+        .flatten()
+        .filter(|(section, _)| {
+            let section = section.trim_matches('/');
+            parent == section || parent.starts_with(&format!("{section}/"))
+        })
+        .max_by_key(|(section, _)| section.trim_matches('/').len())
+        .map(|(_, pattern)| pattern.as_str())
+        .unwrap_or(&config.build.permalinks.pattern)
+}
+
+fn bare_path(path: &str) -> &str {
+    let bare = path.trim_end_matches('/');
+    bare.strip_suffix(".html").unwrap_or(bare)
+}
+
+pub fn apply_style(style: UrlStyle, path: &str) -> String {
+    match (style, bare_path(path)) {
+        (_, "") => "/".to_string(),
+        (UrlStyle::TrailingSlash, bare) => format!("{bare}/"),
+        (UrlStyle::NoTrailingSlash, bare) => bare.to_string(),
+        (UrlStyle::Html, bare) => format!("{bare}.html"),
+    }
+}
+
+pub fn styled_path(config: &IlgiConfig, path: &str) -> String {
+    apply_style(config.build.permalinks.style, path)
+}
+
+pub fn directory_path(path: &str) -> String {
+    format!("{}/", bare_path(path))
+}
+
+pub fn article_path(config: &IlgiConfig, timezone: &Tz, article: &Article) -> IResult<String> {
+    let parent = article.source.rsplit_once('/').map(|(parent, _)| parent).unwrap_or_default();
+    let pattern = section_pattern(config, parent);
+    let invalid = |message: String| IlgiError::InvalidPermalink { path: article.source.clone(), message };
+    let date = article.date.map(|date| date.with_timezone(timezone));
+
+    let mut fields = BTreeMap::new();
+    fields.insert("slug", article.slug.clone());
+    fields.insert("title", url_slug(&config.build.permalinks, &article.title));
+    fields.insert("section", parent.split('/').next().unwrap_or_default().to_string());
+    fields.insert("path", parent.to_string());
+    fields.insert("lang", article.language.clone());
+    if let Some(date) = date {
+        fields.insert("year", format!("{:04}", date.year()));
+        fields.insert("month", format!("{:02}", date.month()));
+        fields.insert("day", format!("{:02}", date.day()));
+    }
+
+    let mut path = String::new();
+    let mut rest = pattern;
+    while let Some(start) = rest.find(':') {
+        path.push_str(&rest[..start]);
+        let name_end = rest[start + 1..].find(|c: char| !c.is_ascii_alphanumeric() && c != '_').map_or(rest.len(), |end| start + 1 + end);
+        let name = &rest[start + 1..name_end];
+        match fields.get(name) {
+            Some(value) => path.push_str(value),
+            None if ["year", "month", "day"].contains(&name) => {
+                return Err(invalid(format!("the pattern {pattern} uses :{name} but the page has no date")).into());
+            }
+            None => return Err(invalid(format!("unknown placeholder :{name} in {pattern}")).into()),
+        }
+        rest = &rest[name_end..];
+    }
+    path.push_str(rest);
+
+    let path = path.split('/').filter(|segment| !segment.is_empty()).join("/");
+    let prefix = match pattern.contains(":lang") {
+        true => String::new(),
+        false => config.language_prefix(&article.language),
+    };
+    Ok(styled_path(config, &format!("{prefix}/{path}")))
+}
+
+pub fn check_duplicate_urls(pages: impl IntoIterator<Item = (String, String)>) -> IResult<()> {
+    let mut seen = BTreeMap::<String, Vec<String>>::new();
+    for (path, source) in pages {
+        seen.entry(path).or_default().push(source);
+    }
+    match seen.into_iter().find(|(_, sources)| sources.len() > 1) {
+        Some((url, sources)) => Err(IlgiError::DuplicateUrl { url, sources: sources.join(", ") }.into()),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use crate::config::test_config;
+    use super::*;
+
+    fn article(source: &str, date: Option<&str>) -> Article {
+        Article {
+            source: source.to_string(),
+            slug: "hello".to_string(),
+            title: "Hello, World".to_string(),
+            language: "en".to_string(),
+            date: date.map(|date| DateTime::parse_from_rfc3339(date).unwrap()),
+            ..Default::default()
+        }
+    }
+
+    fn path(config: &IlgiConfig, article: &Article) -> IResult<String> {
+        article_path(config, &config.tz()?, article)
+    }
+
+    #[test]
+    fn styles_paths() {
+        assert_eq!(apply_style(UrlStyle::TrailingSlash, "/blog/post.html"), "/blog/post/");
+        assert_eq!(apply_style(UrlStyle::NoTrailingSlash, "/blog/post/"), "/blog/post");
+        assert_eq!(apply_style(UrlStyle::Html, "/blog/post/"), "/blog/post.html");
+        assert_eq!(apply_style(UrlStyle::Html, "/"), "/");
+        assert_eq!(directory_path("/blog/post.html"), "/blog/post/");
+    }
+
+    #[test]
+    fn fills_pattern_placeholders() {
+        let mut config = test_config();
+        config.timezone = "UTC".to_string();
+        config.build.permalinks.style = UrlStyle::TrailingSlash;
+        config.build.permalinks.pattern = "/:path/:slug/".to_string();
+        assert_eq!(path(&config, &article("blog/rust/hello.md", None)).unwrap(), "/blog/rust/hello/");
+        assert_eq!(path(&config, &article("hello.md", None)).unwrap(), "/hello/");
+
+        config.build.permalinks.pattern = "/:section/:year/:month/:day/:title".to_string();
+        config.build.permalinks.style = UrlStyle::Html;
+        let dated = article("blog/rust/hello.md", Some("2024-02-29T23:30:00Z"));
+        assert_eq!(path(&config, &dated).unwrap(), "/blog/2024/02/29/hello-world.html");
+
+        config.timezone = "Asia/Seoul".to_string();
+        assert_eq!(path(&config, &dated).unwrap(), "/blog/2024/03/01/hello-world.html");
+    }
+
+    #[test]
+    fn prefers_the_longest_matching_section_pattern() {
+        let mut config = test_config();
+        config.build.permalinks.style = UrlStyle::TrailingSlash;
+        config.build.permalinks.pattern = "/:slug/".to_string();
+        config.build.permalinks.sections = Some(BTreeMap::from([
+            ("blog".to_string(), "/posts/:slug/".to_string()),
+            ("blog/notes".to_string(), "/notes/:slug/".to_string()),
+        ]));
+        assert_eq!(path(&config, &article("blog/notes/hello.md", None)).unwrap(), "/notes/hello/");
+        assert_eq!(path(&config, &article("blog/hello.md", None)).unwrap(), "/posts/hello/");
+        assert_eq!(path(&config, &article("blogroll/hello.md", None)).unwrap(), "/hello/");
+    }
+
+    #[test]
+    fn rejects_unknown_placeholders_and_missing_dates() {
+        let mut config = test_config();
+        config.build.permalinks.pattern = "/:year/:slug/".to_string();
+        assert!(path(&config, &article("hello.md", None)).is_err());
+        config.build.permalinks.pattern = "/:author/:slug/".to_string();
+        assert!(path(&config, &article("hello.md", None)).is_err());
+    }
+
+    #[test]
+    fn reports_duplicate_urls() {
+        let pages = [("/a/", "a.md"), ("/b/", "b.md"), ("/a/", "a.html")].map(|(path, source)| (path.to_string(), source.to_string()));
+        assert!(check_duplicate_urls(pages.clone().into_iter().take(2)).is_ok());
+        assert!(check_duplicate_urls(pages).is_err());
+    }
+}
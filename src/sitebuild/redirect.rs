@@ -0,0 +1,149 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+use miette::WrapErr;
+use relative_path::RelativePath;
+use serde::Serialize;
+use tera::Context;
+use ilgi_core::error::{IlgiError, IResult};
+use crate::config::IlgiConfig;
+use crate::file_ops::write_output;
+use crate::sitebuild::absolute_url;
+use crate::sitebuild::article::Article;
+use crate::sitebuild::language::home_path;
+use crate::sitebuild::render::render_template_or;
+use crate::theme::Theme;
+
+pub const NETLIFY_FILE: &str = "_redirects";
+pub const NGINX_FILE: &str = "redirects.conf";
+pub const REDIRECT_TEMPLATE: &str = "redirect.html";
+
+const DEFAULT_REDIRECT_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Redirecting…</title>
+<link rel="canonical" href="{{ url }}">
+<meta name="robots" content="noindex">
+<meta http-equiv="refresh" content="0; url={{ url }}">
+</head>
+<body><a href="{{ url }}">{{ url }}</a></body>
+</html>
+"#;
+
+const REDIRECT_STATUSES: [u16; 5] = [301, 302, 303, 307, 308];
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Redirect {
+    pub from: String,
+    pub to: String,
+    pub status: u16,
+}
+
+fn normalize_from(config: &IlgiConfig, from: &str) -> IResult<String> {
+    let from = from.trim();
+    let base_url = config.base_url.trim_end_matches('/');
+    let path = match from.strip_prefix(base_url) {
+        Some(path) if !base_url.is_empty() => path,
+        _ if from.contains("://") => {
+            return Err(IlgiError::InvalidRedirect { from: from.to_string(), message: "redirects can only be made from paths on this site".to_string() }.into());
+        }
+        _ => from,
+    };
+    Ok(format!("/{}", path.trim_start_matches('/')))
+}
+
+fn same_path(a: &str, b: &str) -> bool {
+    a.trim_end_matches('/') == b.trim_end_matches('/')
+}
+
+pub fn collect_redirects(config: &IlgiConfig, articles: &[&Article]) -> IResult<Vec<Redirect>> {
+    let rules = articles.iter()
+        .flat_map(|article| article.aliases.iter().map(|alias| (alias.clone(), article.path.clone(), None)))
+        .chain(
+            config.build.redirects.rules.iter()
+                .flatten()
+                .map(|rule| (rule.from.clone(), rule.to.clone(), rule.status))
+        );
+
+    let mut redirects = BTreeMap::<String, Redirect>::new();
+    for (from, to, status) in rules {
+        let from = normalize_from(config, &from)?;
+        let status = status.unwrap_or(config.build.redirects.status);
+        let invalid = |message: String| IlgiError::InvalidRedirect { from: from.clone(), message };
+
+        if !REDIRECT_STATUSES.contains(&status) {
+            return Err(invalid(format!("{status} is not a redirect status, use one of 301, 302, 303, 307 or 308")).into());
+        }
+        if same_path(&from, &to) {
+            return Err(invalid("it redirects to itself".to_string()).into());
+        }
+        if let Some(article) = articles.iter().find(|article| same_path(&article.path, &from)) {
+            return Err(invalid(format!("it would replace {}", article.source)).into());
+        }
+        if config.language_codes().into_iter().any(|language| same_path(&home_path(config, language), &from)) {
+            return Err(invalid("it would replace a home page".to_string()).into());
+        }
+
+        let redirect = Redirect { from: from.clone(), to, status };
+        match redirects.get(&from) {
+            Some(existing) if *existing != redirect => {
+                return Err(invalid(format!("it already redirects to {}", existing.to)).into());
+            }
+            _ => {
+                redirects.insert(from, redirect);
+            }
+        }
+    }
+    Ok(redirects.into_values().collect())
+}
+
+pub fn redirect_file(from: &str) -> String {
+    let name = from.rsplit('/').next().unwrap_or_default();
+    match from.ends_with('/') || !name.contains('.') {
+        true => format!("{}/index.html", from.trim_end_matches('/')),
+        false => from.to_string(),
+    }
+}
+
+pub fn render_netlify(redirects: &[Redirect]) -> String {
+    let mut file = String::new();
+    for redirect in redirects {
+        let _ = writeln!(file, "{} {} {}", redirect.from.replace(' ', "%20"), redirect.to.replace(' ', "%20"), redirect.status);
+    }
+    file
+}
+
+pub fn render_nginx(redirects: &[Redirect]) -> String {
+    let mut file = String::from("# server { include redirects.conf; }\n");
+    for redirect in redirects {
+        let _ = writeln!(file, "location = \"{}\" {{ return {} \"{}\"; }}", redirect.from.replace('"', "\\\""), redirect.status, redirect.to.replace('"', "\\\""));
+    }
+    file
+}
+
+pub async fn write_redirects(config: &IlgiConfig, theme: &Theme, output: &Path, redirects: &[Redirect]) -> IResult<()> {
+    if config.build.redirects.html {
+        for redirect in redirects {
+            let file = redirect_file(&redirect.from);
+            if tokio::fs::metadata(RelativePath::new(&file).to_logical_path(output)).await.is_ok() {
+                return Err(IlgiError::InvalidRedirect { from: redirect.from.clone(), message: format!("it would replace the generated {file}") }.into());
+            }
+        }
+        for redirect in redirects {
+            let mut context = Context::new();
+            context.insert("from", &redirect.from);
+            context.insert("url", &absolute_url(config, &redirect.to));
+            let html = render_template_or(theme, REDIRECT_TEMPLATE, DEFAULT_REDIRECT_TEMPLATE, &context)
+                .wrap_err_with(|| format!("while rendering the redirect from {}", redirect.from))?;
+            write_output(output, redirect_file(&redirect.from), html).await?;
+        }
+    }
+    if config.build.redirects.netlify {
+        write_output(output, NETLIFY_FILE, render_netlify(redirects)).await?;
+    }
+    if config.build.redirects.nginx {
+        write_output(output, NGINX_FILE, render_nginx(redirects)).await?;
+    }
+    Ok(())
+}
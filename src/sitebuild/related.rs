@@ -0,0 +1,195 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use rayon::prelude::*;
+use tracing::instrument;
+use crate::config::IlgiConfig;
+use crate::sitebuild::article::Article;
+use crate::sitebuild::slug::normalize_term;
+use crate::sitebuild::search::{body_text, tokenize};
+
+// Title words count this many times as often as body words.
+const TITLE_WEIGHT: usize = 2;
+
+type Vector = Vec<(usize, f64)>;
+
+fn article_terms(article: &Article) -> Vec<String> {
+    let title = tokenize(&article.title);
+    let mut terms = vec![];
+    for _ in 0..TITLE_WEIGHT {
+        terms.extend(title.iter().cloned());
+    }
+    terms.extend(tokenize(&body_text(&article.content)));
+    terms
+}
+
+fn article_labels(article: &Article) -> BTreeSet<(String, String)> {
+    article.taxonomies.iter()
+        .flat_map(|(taxonomy, terms)| terms.iter().map(|term| (taxonomy.clone(), normalize_term(term))))
+        .collect()
+}
+
+fn tf_idf(documents: &[Vec<String>]) -> Vec<Vector> {
+    let counts = documents.par_iter()
+        .map(|terms| {
+            let mut counts = BTreeMap::<&str, usize>::new();
+            for term in terms {
+                *counts.entry(term).or_default() += 1;
+            }
+            counts
+        })
+        .collect::<Vec<_>>();
+
+    let mut frequency = BTreeMap::<&str, usize>::new();
+    for term in counts.iter().flat_map(BTreeMap::keys) {
+        *frequency.entry(term).or_default() += 1;
+    }
+    let ids = frequency.keys().enumerate().map(|(id, term)| (*term, id)).collect::<BTreeMap<_, _>>();
+    let total = documents.len() as f64;
+
+    counts.par_iter()
+        .zip(documents)
+        .map(|(counts, terms)| {
+            let mut vector = counts.iter()
+                .map(|(term, count)| {
+                    let idf = ((total + 1.0) / (frequency[term] as f64 + 1.0)).ln() + 1.0;
+                    (ids[term], *count as f64 / terms.len() as f64 * idf)
+                })
+                .collect::<Vector>();
+            let norm = vector.iter().map(|(_, weight)| weight * weight).sum::<f64>().sqrt();
+            if norm > 0.0 {
+                vector.iter_mut().for_each(|(_, weight)| *weight /= norm);
+            }
+            vector
+        })
+        .collect()
+}
+
+fn cosine(a: &Vector, b: &Vector) -> f64 {
+    let (mut left, mut right) = (a.iter().peekable(), b.iter().peekable());
+    let mut sum = 0.0;
+    while let (Some((a_id, a_weight)), Some((b_id, b_weight))) = (left.peek(), right.peek()) {
+        match a_id.cmp(b_id) {
+            Ordering::Less => { left.next(); }
+            Ordering::Greater => { right.next(); }
+            Ordering::Equal => {
+                sum += a_weight * b_weight;
+                left.next();
+                right.next();
+            }
+        }
+    }
+    sum
+}
+
+fn jaccard(a: &BTreeSet<(String, String)>, b: &BTreeSet<(String, String)>) -> f64 {
+    match a.union(b).count() {
+        0 => 0.0,
+        union => a.intersection(b).count() as f64 / union as f64,
+    }
+}
+
+#[instrument(skip_all)]
+pub fn collect_related(config: &IlgiConfig, articles: &mut [Article]) {
+    let related = &config.build.related;
+    if related.count == 0 {
+        return;
+    }
+    let documents = articles.par_iter().map(article_terms).collect::<Vec<_>>();
+    let vectors = tf_idf(&documents);
+    let labels = articles.par_iter().map(article_labels).collect::<Vec<_>>();
+    let weight = related.taxonomy_weight.clamp(0.0, 1.0) as f64;
+
+    let shared: &[Article] = articles;
+    let ranked = (0..shared.len()).into_par_iter()
+        .map(|index| {
+            let mut scores = (0..shared.len())
+                .filter(|other| *other != index && shared[*other].language == shared[index].language)
+                .map(|other| {
+                    let score = (1.0 - weight) * cosine(&vectors[index], &vectors[other]) + weight * jaccard(&labels[index], &labels[other]);
+                    (score, other)
+                })
+                .filter(|(score, _)| *score > f64::EPSILON)
+                .collect::<Vec<_>>();
+            scores.sort_by(|(a_score, a), (b_score, b)| {
+                b_score.total_cmp(a_score)
+                    .then_with(|| shared[*b].date.cmp(&shared[*a].date))
+                    .then_with(|| shared[*a].source.cmp(&shared[*b].source))
+            });
+            scores.truncate(related.count);
+            scores.into_iter().map(|(_, other)| shared[other].link()).collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    for (article, related) in articles.iter_mut().zip(ranked) {
+        article.related = related;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use crate::config::test_config;
+    use super::*;
+
+    fn article(source: &str, title: &str, content: &str, tags: &[&str], date: &str) -> Article {
+        Article {
+            source: source.to_string(),
+            language: "en".to_string(),
+            path: format!("/{}/", source.trim_end_matches(".md")),
+            title: title.to_string(),
+            content: format!("<p>{content}</p>"),
+            date: DateTime::parse_from_rfc3339(date).ok(),
+            taxonomies: [("tags".to_string(), tags.iter().map(|tag| tag.to_string()).collect())].into_iter().collect(),
+            ..Default::default()
+        }
+    }
+
+    fn related(articles: &mut [Article]) -> Vec<Vec<String>> {
+        let mut config = test_config();
+        config.build.related.count = 3;
+        collect_related(&config, articles);
+        articles.iter()
+            .map(|article| article.related.iter().map(|link| link.title.clone()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn ranks_by_shared_words_and_tags() {
+        let mut articles = vec![
+            article("async.md", "Async Rust", "futures and executors", &["rust"], "2024-01-01T00:00:00Z"),
+            article("traits.md", "Rust traits", "generics and traits", &["rust"], "2024-01-02T00:00:00Z"),
+            article("pasta.md", "Pasta", "boil water, add salt", &["cooking"], "2024-01-03T00:00:00Z"),
+        ];
+        let related = related(&mut articles);
+        assert_eq!(related[0], ["Rust traits"]);
+        assert_eq!(related[1], ["Async Rust"]);
+        assert!(related[2].is_empty());
+    }
+
+    #[test]
+    fn breaks_ties_by_date_then_source() {
+        let articles = || vec![
+            article("a.md", "Sourdough", "flour water starter", &[], "2024-01-01T00:00:00Z"),
+            article("c.md", "Bread", "flour water yeast", &[], "2024-01-02T00:00:00Z"),
+            article("b.md", "Bread", "flour water yeast", &[], "2024-01-02T00:00:00Z"),
+            article("d.md", "Bread", "flour water yeast", &[], "2024-01-05T00:00:00Z"),
+        ];
+        let first = related(&mut articles());
+        assert_eq!(first, related(&mut articles()));
+
+        let mut sourdough = articles();
+        related(&mut sourdough);
+        let sources = sourdough[0].related.iter().map(|link| link.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(sources, ["/d/", "/b/", "/c/"]);
+    }
+
+    #[test]
+    fn title_words_weigh_more_than_body_words() {
+        let mut articles = vec![
+            article("query.md", "Gardening", "tomatoes and basil", &[], "2024-01-01T00:00:00Z"),
+            article("title.md", "Tomatoes", "peppers", &[], "2024-01-02T00:00:00Z"),
+            article("body.md", "Peppers", "tomatoes", &[], "2024-01-03T00:00:00Z"),
+        ];
+        assert_eq!(related(&mut articles)[0], ["Tomatoes", "Peppers"]);
+    }
+}
@@ -0,0 +1,99 @@
+use std::path::Path;
+use miette::{IntoDiagnostic, WrapErr};
+use serde::Serialize;
+use tera::{Context, Tera};
+use ilgi_core::error::IResult;
+use crate::config::IlgiConfig;
+use crate::file_ops::write_output;
+use crate::i18n::with_page_language;
+use crate::sitebuild::language::{article_switcher, hreflang_links};
+use crate::sitebuild::section::article_section;
+use crate::sitebuild::Site;
+use crate::theme::Theme;
+
+pub const PAGE_TEMPLATE: &str = "page.html";
+pub const UNPUBLISHED_TEMPLATE: &str = "partials/unpublished.html";
+
+const DEFAULT_UNPUBLISHED_TEMPLATE: &str = r#"<div class="unpublished-banner" style="position:sticky;top:0;z-index:1000;padding:0.5em 1em;background:#fde68a;color:#78350f;font:14px/1.4 sans-serif">{% if page.draft %}Draft{% elif page.expired %}Expired{% else %}Scheduled for {{ page.date }}{% endif %}: this page is not visible on the published site.</div>
+"#;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SiteContext<'a> {
+    pub title: &'a str,
+    pub description: Option<&'a str>,
+    pub base_url: &'a str,
+    pub language: &'a str,
+}
+
+pub fn base_context(config: &IlgiConfig, language: &str) -> Context {
+    let mut context = Context::new();
+    context.insert("site", &SiteContext {
+        title: config.site_title(language),
+        description: config.site_description(language),
+        base_url: &config.base_url,
+        language,
+    });
+    context
+}
+
+fn page_language(context: &Context) -> Option<&str> {
+    context.get("page")
+        .and_then(|page| page.get("language"))
+        .or_else(|| context.get("site").and_then(|site| site.get("language")))
+        .and_then(|language| language.as_str())
+}
+
+pub fn render_template(theme: &Theme, template: &str, context: &Context) -> IResult<String> {
+    with_page_language(page_language(context), || theme.tera.render(template, context))
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to render template {template}"))
+}
+
+pub fn render_template_or(theme: &Theme, template: &str, fallback: &str, context: &Context) -> IResult<String> {
+    if theme.tera.get_template_names().any(|name| name == template) {
+        return render_template(theme, template, context);
+    }
+    with_page_language(page_language(context), || Tera::one_off(fallback, context, true))
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to render the built-in {template}"))
+}
+
+pub fn output_file(path: &str) -> String {
+    match path.rsplit('/').next() {
+        _ if path.ends_with('/') => format!("{path}index.html"),
+        Some(name) if !name.contains('.') => format!("{path}.html"),
+        _ => path.to_string(),
+    }
+}
+
+fn insert_banner(html: String, banner: &str) -> String {
+    let body = html.to_ascii_lowercase()
+        .find("<body")
+        .and_then(|start| html[start..].find('>').map(|end| start + end + 1));
+    match body {
+        Some(index) => format!("{}{banner}{}", &html[..index], &html[index..]),
+        None => format!("{banner}{html}"),
+    }
+}
+
+pub async fn write_articles(config: &IlgiConfig, theme: &Theme, site: &Site, output: &Path) -> IResult<()> {
+    for article in site.articles.iter().chain(&site.unlisted) {
+        let switcher = article_switcher(config, site, article);
+        let mut context = base_context(config, &article.language);
+        context.insert("page", article);
+        if let Some(section) = article_section(site, article) {
+            context.insert("section", &section.context(site));
+        }
+        context.insert("translations", &site.translations(article).collect::<Vec<_>>());
+        context.insert("hreflang", &hreflang_links(config, &switcher));
+        context.insert("language_switcher", &switcher);
+        let html = render_template(theme, article.template.as_deref().unwrap_or(PAGE_TEMPLATE), &context)
+            .wrap_err_with(|| format!("while rendering {}", article.source))?;
+        let html = match config.build.drafts && !article.published {
+            true => insert_banner(html, &render_template_or(theme, UNPUBLISHED_TEMPLATE, DEFAULT_UNPUBLISHED_TEMPLATE, &context)?),
+            false => html,
+        };
+        write_output(output, output_file(&article.path), html).await?;
+    }
+    Ok(())
+}
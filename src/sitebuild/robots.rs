@@ -0,0 +1,25 @@
+use std::fmt::Write;
+use crate::config::IlgiConfig;
+use crate::sitebuild::absolute_url;
+use crate::sitebuild::sitemap;
+
+pub const FILE_NAME: &str = "robots.txt";
+
+pub fn render_robots(config: &IlgiConfig) -> String {
+    let mut robots = String::from("User-agent: *\n");
+    if config.build.robots.index_environments.contains(&config.environment) {
+        if config.build.robots.disallow.is_empty() {
+            robots.push_str("Allow: /\n");
+        }
+        for path in &config.build.robots.disallow {
+            let _ = writeln!(robots, "Disallow: {path}");
+        }
+    } else {
+        robots.push_str("Disallow: /\n");
+    }
+
+    if config.build.sitemap.enabled {
+        let _ = write!(robots, "\nSitemap: {}\n", absolute_url(config, sitemap::FILE_NAME));
+    }
+    robots
+}
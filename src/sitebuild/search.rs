@@ -0,0 +1,198 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use chrono::{DateTime, FixedOffset};
+use itertools::Itertools;
+use miette::IntoDiagnostic;
+use rayon::prelude::*;
+use serde::Serialize;
+use ilgi_core::error::IResult;
+use crate::config::IlgiConfig;
+use crate::file_ops::{add_hash_filename, write_output};
+use crate::formatting::is_cjk;
+use crate::sitebuild::absolute_url;
+use crate::sitebuild::article::Article;
+use crate::sitebuild::markdown::TocEntry;
+use crate::sitebuild::slug::normalize_term;
+use crate::sitebuild::summary::{decode_entities, visible_tokens, HtmlToken};
+
+pub const MANIFEST_FILE: &str = "index.json";
+
+const TITLE_WEIGHT: usize = 10;
+const HEADING_WEIGHT: usize = 5;
+const TAG_WEIGHT: usize = 5;
+const BODY_WEIGHT: usize = 1;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SearchDocument<'a> {
+    pub url: &'a str,
+    pub title: &'a str,
+    pub summary: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<DateTime<FixedOffset>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<&'a str>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SearchIndex<'a> {
+    pub language: &'a str,
+    pub documents: Vec<SearchDocument<'a>>,
+    pub index: BTreeMap<String, Vec<(usize, usize)>>,
+}
+
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    for word in text.split(|character: char| !character.is_alphanumeric()).filter(|word| !word.is_empty()) {
+        let word = normalize_term(word);
+        for (cjk, run) in &word.chars().group_by(|character| is_cjk(*character)) {
+            let run = run.collect::<Vec<_>>();
+            match (cjk, run.len()) {
+                (_, 0) | (false, 1) => {}
+                (false, _) => tokens.push(run.into_iter().collect()),
+                (true, 1) => tokens.push(run[0].to_string()),
+                (true, _) => tokens.extend(run.windows(2).map(|pair| pair.iter().collect())),
+            }
+        }
+    }
+    tokens
+}
+
+pub fn body_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut preformatted = 0usize;
+    for token in visible_tokens(html) {
+        match token {
+            HtmlToken::Open { name, void: false, .. } if name == "pre" => preformatted += 1,
+            HtmlToken::Close { name, .. } if name == "pre" => preformatted = preformatted.saturating_sub(1),
+            HtmlToken::Text(content) if preformatted == 0 => text.push_str(&decode_entities(content)),
+            HtmlToken::Open { .. } | HtmlToken::Close { .. } if !text.ends_with(' ') => text.push(' '),
+            _ => {}
+        }
+    }
+    text
+}
+
+pub fn truncate_chars(text: &str, limit: usize) -> &str {
+    text.char_indices().nth(limit).map_or(text, |(end, _)| &text[..end])
+}
+
+pub fn heading_titles(entries: &[TocEntry]) -> Vec<String> {
+    entries.iter()
+        .flat_map(|entry| std::iter::once(entry.title.clone()).chain(heading_titles(&entry.children)))
+        .collect()
+}
+
+fn document_tokens(config: &IlgiConfig, article: &Article) -> BTreeMap<String, usize> {
+    let mut weights = BTreeMap::<String, usize>::new();
+    let mut add = |text: &str, weight: usize| {
+        for token in tokenize(text) {
+            *weights.entry(token).or_default() += weight;
+        }
+    };
+    add(&article.title, TITLE_WEIGHT);
+    heading_titles(&article.toc).iter().for_each(|title| add(title, HEADING_WEIGHT));
+    article.taxonomies.values().flatten().for_each(|term| add(term, TAG_WEIGHT));
+    add(truncate_chars(&body_text(&article.content), config.build.search.max_body_chars), BODY_WEIGHT);
+    weights
+}
+
+pub fn build_index<'a>(config: &IlgiConfig, language: &'a str, articles: &'a [Article]) -> SearchIndex<'a> {
+    let articles = articles.iter()
+        .filter(|article| article.searchable && article.language == language)
+        .collect::<Vec<_>>();
+    let tokens = articles.par_iter()
+        .map(|article| document_tokens(config, article))
+        .collect::<Vec<_>>();
+
+    let mut index = BTreeMap::<String, Vec<(usize, usize)>>::new();
+    for (document, tokens) in tokens.into_iter().enumerate() {
+        for (token, weight) in tokens {
+            index.entry(token).or_default().push((document, weight));
+        }
+    }
+
+    let documents = articles.iter()
+        .map(|article| SearchDocument {
+            url: &article.permalink,
+            title: &article.title,
+            summary: truncate_chars(article.description.as_deref().unwrap_or(&article.summary_text), config.build.search.max_summary_chars).to_string(),
+            date: article.date,
+            tags: article.terms("tags").iter().map(String::as_str).collect(),
+        })
+        .collect();
+    SearchIndex { language, documents, index }
+}
+
+pub async fn write_search_indexes(config: &IlgiConfig, output: &Path, articles: &[Article]) -> IResult<()> {
+    let directory = config.build.search.path.trim_matches('/');
+    let mut manifest = BTreeMap::new();
+    for language in config.language_codes() {
+        let index = build_index(config, language, articles);
+        let json = serde_json::to_string(&index).into_diagnostic()?;
+        let file = add_hash_filename(format!("{language}.json"), &json);
+        manifest.insert(language, absolute_url(config, &format!("/{directory}/{file}")));
+        write_output(output, format!("{directory}/{file}"), json).await?;
+    }
+    write_output(output, format!("{directory}/{MANIFEST_FILE}"), serde_json::to_string(&manifest).into_diagnostic()?).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::test_config;
+    use super::*;
+
+    #[test]
+    fn splits_cjk_runs_into_bigrams() {
+        assert_eq!(tokenize("검색엔진"), ["검색", "색엔", "엔진"]);
+        assert_eq!(tokenize("東京 に"), ["東京", "に"]);
+        assert_eq!(tokenize("Rust로 만든 블로그"), ["rust", "로", "만든", "블로", "로그"]);
+    }
+
+    #[test]
+    fn lowercases_words_and_drops_single_letters() {
+        assert_eq!(tokenize("Hello, WORLD! a 42 x-ray"), ["hello", "world", "42", "ray"]);
+        assert_eq!(tokenize("Ｒｕｓｔ"), ["rust"]);
+    }
+
+    #[test]
+    fn indexes_visible_prose_only() {
+        let text = body_text("<h1>Title</h1><p>Fish &amp; chips</p><pre><code>let x = 1;</code></pre><script>track()</script><p>end</p>");
+        assert_eq!(text.split_whitespace().collect::<Vec<_>>(), ["Title", "Fish", "&", "chips", "end"]);
+    }
+
+    #[test]
+    fn truncates_on_character_boundaries() {
+        assert_eq!(truncate_chars("한국어 글", 2), "한국");
+        assert_eq!(truncate_chars("short", 10), "short");
+    }
+
+    #[test]
+    fn weighs_titles_headings_tags_and_body() {
+        let mut config = test_config();
+        config.build.search.max_body_chars = 10000;
+        config.build.search.max_summary_chars = 5;
+        let articles = [
+            Article {
+                title: "Rust notes".to_string(),
+                language: "en".to_string(),
+                searchable: true,
+                toc: vec![TocEntry { level: 2, id: "ownership".to_string(), title: "Ownership".to_string(), children: vec![] }],
+                taxonomies: BTreeMap::from([("tags".to_string(), vec!["rust".to_string()])]),
+                content: "<p>rust ownership rules</p>".to_string(),
+                summary_text: "Rust ownership rules".to_string(),
+                ..Default::default()
+            },
+            Article { title: "Hidden".to_string(), language: "en".to_string(), searchable: false, ..Default::default() },
+            Article { title: "Korean".to_string(), language: "ko".to_string(), searchable: true, ..Default::default() },
+        ];
+        let index = build_index(&config, "en", &articles);
+
+        assert_eq!(index.documents.len(), 1);
+        assert_eq!(index.documents[0].summary, "Rust ");
+        assert_eq!(index.documents[0].tags, ["rust"]);
+        assert_eq!(index.index["rust"], [(0, TITLE_WEIGHT + TAG_WEIGHT + BODY_WEIGHT)]);
+        assert_eq!(index.index["ownership"], [(0, HEADING_WEIGHT + BODY_WEIGHT)]);
+        assert_eq!(index.index["rules"], [(0, BODY_WEIGHT)]);
+    }
+}
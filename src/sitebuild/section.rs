@@ -0,0 +1,488 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use miette::{IntoDiagnostic, WrapErr};
+use rayon::prelude::*;
+use relative_path::RelativePath;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use ilgi_core::error::IResult;
+use crate::config::IlgiConfig;
+use crate::file_ops::{load_dir, write_output};
+use crate::sitebuild::absolute_url;
+use crate::sitebuild::article::{render_content, split_front_matter, Article};
+use crate::sitebuild::language::home_path;
+use crate::sitebuild::markdown::{InternalLink, TocEntry};
+use crate::sitebuild::pagination::paginate;
+use crate::sitebuild::permalink::styled_path;
+use crate::sitebuild::render::{base_context, output_file, render_template, render_template_or};
+use crate::sitebuild::sitemap::SitemapEntry;
+use crate::sitebuild::Site;
+use crate::theme::Theme;
+
+pub const SECTION_INDEX: &str = "_index";
+pub const SECTION_TEMPLATE: &str = "section.html";
+
+const DEFAULT_SECTION_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html lang="{{ site.language }}">
+<head><meta charset="utf-8"><title>{{ section.title }} - {{ site.title }}</title>{{ paginator.rel_links | safe }}</head>
+<body>
+<h1>{{ section.title }}</h1>
+{{ section.content | safe }}
+{% if section.subsections %}<ul>{% for subsection in section.subsections %}<li><a href="{{ subsection.permalink }}">{{ subsection.title }}</a></li>{% endfor %}</ul>{% endif %}
+<ul>{% for page in paginator.pages %}<li><a href="{{ page.permalink }}">{{ page.title }}</a></li>{% endfor %}</ul>
+</body>
+</html>
+"#;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+    #[default]
+    Date,
+    Title,
+    Weight,
+    None,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SectionFrontMatter {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub sort_by: SortBy,
+    #[serde(default)]
+    pub weight: i64,
+    #[serde(default)]
+    pub template: Option<String>,
+    #[serde(default)]
+    pub page_template: Option<String>,
+    #[serde(default)]
+    pub paginate_by: Option<usize>,
+    #[serde(default)]
+    pub transparent: bool,
+    #[serde(default)]
+    pub extra: toml::Table,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SectionLink {
+    pub title: String,
+    pub path: String,
+    pub permalink: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Section {
+    pub directory: String,
+    pub language: String,
+    pub source: Option<String>,
+    pub title: String,
+    pub description: Option<String>,
+    pub path: String,
+    pub permalink: String,
+    pub sort_by: SortBy,
+    pub weight: i64,
+    pub paginate_by: Option<usize>,
+    pub transparent: bool,
+    pub extra: toml::Table,
+    pub content: String,
+    pub toc: Vec<TocEntry>,
+    pub ancestors: Vec<SectionLink>,
+    #[serde(skip)]
+    pub template: Option<String>,
+    #[serde(skip)]
+    pub page_template: Option<String>,
+    #[serde(skip)]
+    pub parent: Option<usize>,
+    #[serde(skip)]
+    pub pages: Vec<usize>,
+    #[serde(skip)]
+    pub subsections: Vec<usize>,
+    #[serde(skip)]
+    pub links: Vec<InternalLink>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SectionContext<'a> {
+    #[serde(flatten)]
+    pub section: &'a Section,
+    pub pages: Vec<&'a Article>,
+    pub subsections: Vec<&'a Section>,
+}
+
+struct SectionIndex {
+    source: String,
+    front_matter: SectionFrontMatter,
+    content: String,
+    toc: Vec<TocEntry>,
+    links: Vec<InternalLink>,
+}
+
+pub fn is_section_index(path: &Path) -> bool {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .is_some_and(|stem| stem == SECTION_INDEX || stem.starts_with(&format!("{SECTION_INDEX}.")))
+}
+
+fn parent_directory(directory: &str) -> Option<&str> {
+    match directory {
+        "" => None,
+        directory => Some(directory.rsplit_once('/').map(|(parent, _)| parent).unwrap_or_default()),
+    }
+}
+
+fn article_directory(article: &Article) -> &str {
+    article.source.rsplit_once('/').map(|(parent, _)| parent).unwrap_or_default()
+}
+
+impl Section {
+    pub fn link(&self) -> SectionLink {
+        SectionLink {
+            title: self.title.clone(),
+            path: self.path.clone(),
+            permalink: self.permalink.clone(),
+        }
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.directory.is_empty()
+    }
+
+    pub fn context<'a>(&'a self, site: &'a Site) -> SectionContext<'a> {
+        SectionContext {
+            section: self,
+            pages: self.pages.iter().map(|index| &site.articles[*index]).collect(),
+            subsections: self.subsections.iter().map(|index| &site.sections[*index]).collect(),
+        }
+    }
+}
+
+fn load_section_index(config: &IlgiConfig, theme: &Theme, directory: &Path, path: &Path) -> IResult<((String, String), SectionIndex)> {
+    let relative = RelativePath::from_path(path.strip_prefix(directory).into_diagnostic()?)
+        .into_diagnostic()?
+        .to_relative_path_buf();
+    let data = std::fs::read_to_string(path).into_diagnostic()?;
+    let (front_matter, body) = match split_front_matter(&data) {
+        Some((front_matter, body)) => (
+            toml::from_str::<SectionFrontMatter>(front_matter)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("invalid section front matter in {relative}"))?,
+            body,
+        ),
+        None => (SectionFrontMatter::default(), data.as_str()),
+    };
+
+    let language = relative.file_stem()
+        .and_then(|stem| stem.strip_prefix(&format!("{SECTION_INDEX}.")))
+        .filter(|language| config.is_language(language))
+        .unwrap_or(&config.default_language)
+        .to_string();
+    let rendered = render_content(config, theme, &relative, &data, body)?;
+
+    let parent = relative.parent().map(RelativePath::as_str).unwrap_or_default().to_string();
+    Ok((
+        (parent, language),
+        SectionIndex {
+            source: relative.to_string(),
+            front_matter,
+            content: rendered.html,
+            toc: rendered.toc,
+            links: rendered.links,
+        },
+    ))
+}
+
+fn sort_pages(articles: &[Article], pages: &mut [usize], sort_by: SortBy) {
+    pages.sort_by(|a, b| {
+        let (a, b) = (&articles[*a], &articles[*b]);
+        match sort_by {
+            SortBy::Date => b.date.cmp(&a.date),
+            SortBy::Title => a.title.cmp(&b.title),
+            SortBy::Weight => a.weight.unwrap_or(i64::MAX).cmp(&b.weight.unwrap_or(i64::MAX)).then_with(|| a.title.cmp(&b.title)),
+            SortBy::None => std::cmp::Ordering::Equal,
+        }
+        .then_with(|| a.source.cmp(&b.source))
+    });
+}
+
+#[instrument(skip(config, theme, articles))]
+pub fn collect_sections(config: &IlgiConfig, theme: &Theme, directory: &Path, articles: &mut [Article]) -> IResult<Vec<Section>> {
+    let indexes = load_dir(directory)?
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "md") && is_section_index(path))
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .map(|path| load_section_index(config, theme, directory, &path))
+        .collect::<IResult<BTreeMap<_, _>>>()?;
+    Ok(build_sections(config, &indexes, articles))
+}
+
+fn build_sections(config: &IlgiConfig, indexes: &BTreeMap<(String, String), SectionIndex>, articles: &mut [Article]) -> Vec<Section> {
+    let mut keys = BTreeSet::new();
+    let directories = articles.iter()
+        .map(|article| (article_directory(article).to_string(), article.language.clone()))
+        .chain(indexes.keys().cloned())
+        .collect::<Vec<_>>();
+    for (directory, language) in directories {
+        let mut current = Some(directory.as_str());
+        while let Some(directory) = current {
+            keys.insert((directory.to_string(), language.clone()));
+            current = parent_directory(directory);
+        }
+    }
+
+    let positions = keys.iter()
+        .enumerate()
+        .map(|(index, key)| (key.clone(), index))
+        .collect::<BTreeMap<_, _>>();
+    let mut sections = keys.into_iter()
+        .map(|(directory, language)| {
+            let index = indexes.get(&(directory.clone(), language.clone()));
+            let front_matter = index.map(|index| index.front_matter.clone()).unwrap_or_default();
+            let path = match directory.as_str() {
+                "" => home_path(config, &language),
+                directory => styled_path(config, &format!("{}/{directory}/", config.language_prefix(&language))),
+            };
+            let title = front_matter.title.unwrap_or_else(|| match directory.rsplit('/').next() {
+                Some("") | None => config.site_title(&language).to_string(),
+                Some(name) => name.to_string(),
+            });
+            Section {
+                parent: parent_directory(&directory).and_then(|parent| positions.get(&(parent.to_string(), language.clone())).copied()),
+                source: index.map(|index| index.source.clone()),
+                permalink: absolute_url(config, &path),
+                path,
+                title,
+                description: front_matter.description,
+                sort_by: front_matter.sort_by,
+                weight: front_matter.weight,
+                paginate_by: front_matter.paginate_by,
+                transparent: front_matter.transparent,
+                extra: front_matter.extra,
+                content: index.map(|index| index.content.clone()).unwrap_or_default(),
+                toc: index.map(|index| index.toc.clone()).unwrap_or_default(),
+                links: index.map(|index| index.links.clone()).unwrap_or_default(),
+                ancestors: vec![],
+                template: front_matter.template,
+                page_template: front_matter.page_template,
+                pages: vec![],
+                subsections: vec![],
+                directory,
+                language,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    for (index, article) in articles.iter().enumerate() {
+        if let Some(section) = positions.get(&(article_directory(article).to_string(), article.language.clone())) {
+            sections[*section].pages.push(index);
+        }
+    }
+    for index in 0..sections.len() {
+        let mut ancestors = vec![];
+        let mut current = sections[index].parent;
+        while let Some(parent) = current {
+            if !sections[parent].is_root() {
+                ancestors.push(sections[parent].link());
+            }
+            current = sections[parent].parent;
+        }
+        ancestors.reverse();
+        sections[index].ancestors = ancestors;
+        if let Some(parent) = sections[index].parent {
+            sections[parent].subsections.push(index);
+        }
+    }
+
+    for section in 0..sections.len() {
+        let sort_by = sections[section].sort_by;
+        sort_pages(articles, &mut sections[section].pages, sort_by);
+
+        let mut ancestors = sections[section].ancestors.clone();
+        if !sections[section].is_root() {
+            ancestors.push(sections[section].link());
+        }
+        let mut page_template = None;
+        let mut current = Some(section);
+        while let (None, Some(index)) = (&page_template, current) {
+            page_template = sections[index].page_template.clone();
+            current = sections[index].parent;
+        }
+
+        let pages = sections[section].pages.clone();
+        for (position, page) in pages.iter().enumerate() {
+            let previous = position.checked_sub(1).map(|previous| articles[pages[previous]].link());
+            let next = pages.get(position + 1).map(|next| articles[*next].link());
+            let article = &mut articles[*page];
+            article.ancestors = ancestors.clone();
+            article.previous = previous;
+            article.next = next;
+            article.template = page_template.clone();
+        }
+    }
+
+    let mut by_depth = (0..sections.len()).collect::<Vec<_>>();
+    by_depth.sort_by_key(|index| std::cmp::Reverse(sections[*index].directory.matches('/').count() + !sections[*index].is_root() as usize));
+    for index in by_depth {
+        if let (true, Some(parent)) = (sections[index].transparent, sections[index].parent) {
+            let pages = sections[index].pages.clone();
+            sections[parent].pages.extend(pages);
+            let sort_by = sections[parent].sort_by;
+            sort_pages(articles, &mut sections[parent].pages, sort_by);
+        }
+    }
+
+    for section in 0..sections.len() {
+        let mut subsections = std::mem::take(&mut sections[section].subsections);
+        subsections.sort_by(|a, b| sections[*a].weight.cmp(&sections[*b].weight).then_with(|| sections[*a].title.cmp(&sections[*b].title)));
+        sections[section].subsections = subsections;
+    }
+    sections
+}
+
+pub fn article_section<'a>(site: &'a Site, article: &Article) -> Option<&'a Section> {
+    site.sections.iter().find(|section| section.directory == article_directory(article) && section.language == article.language)
+}
+
+pub fn sitemap_entries<'a>(site: &'a Site) -> impl Iterator<Item = SitemapEntry> + 'a {
+    site.sections.iter()
+        .filter(|section| !section.is_root())
+        .map(|section| SitemapEntry {
+            loc: section.permalink.clone(),
+            lastmod: section.pages.iter().filter_map(|index| site.last_modified(&site.articles[*index])).max(),
+            alternates: vec![],
+        })
+}
+
+pub async fn write_sections(config: &IlgiConfig, theme: &Theme, site: &Site, output: &Path) -> IResult<()> {
+    for section in site.sections.iter().filter(|section| !section.is_root()) {
+        let context_section = section.context(site);
+        for paginator in paginate(config, &section.path, &context_section.pages, Some(section.paginate_by.unwrap_or(0))) {
+            let mut context = base_context(config, &section.language);
+            context.insert("section", &context_section);
+            context.insert("paginator", &paginator);
+            let html = match &section.template {
+                Some(template) => render_template(theme, template, &context),
+                None => render_template_or(theme, SECTION_TEMPLATE, DEFAULT_SECTION_TEMPLATE, &context),
+            }
+            .wrap_err_with(|| format!("while rendering the {} section", section.directory))?;
+            write_output(output, output_file(&paginator.path), html).await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use crate::config::{test_config, UrlStyle};
+    use super::*;
+
+    fn article(source: &str, date: &str) -> Article {
+        Article {
+            source: source.to_string(),
+            title: source.to_string(),
+            path: format!("/{}/", source.trim_end_matches(".md")),
+            language: "en".to_string(),
+            date: Some(DateTime::parse_from_rfc3339(date).unwrap()),
+            ..Default::default()
+        }
+    }
+
+    fn index(directory: &str, front_matter: &str) -> ((String, String), SectionIndex) {
+        (
+            (directory.to_string(), "en".to_string()),
+            SectionIndex {
+                source: format!("{directory}/_index.md"),
+                front_matter: toml::from_str(front_matter).unwrap(),
+                content: String::new(),
+                toc: vec![],
+                links: vec![],
+            },
+        )
+    }
+
+    fn config() -> IlgiConfig {
+        let mut config = test_config();
+        config.title = "Test".to_string();
+        config.default_language = "en".to_string();
+        config.build.permalinks.style = UrlStyle::TrailingSlash;
+        config
+    }
+
+    fn section<'a>(sections: &'a [Section], directory: &str) -> &'a Section {
+        sections.iter().find(|section| section.directory == directory).unwrap()
+    }
+
+    fn sources<'a>(articles: &'a [Article], section: &Section) -> Vec<&'a str> {
+        section.pages.iter().map(|page| articles[*page].source.as_str()).collect()
+    }
+
+    #[test]
+    fn builds_the_tree_from_article_directories() {
+        let mut articles = [
+            article("about.md", "2024-01-01T00:00:00Z"),
+            article("blog/rust/borrowing.md", "2024-03-01T00:00:00Z"),
+        ];
+        let sections = build_sections(&config(), &BTreeMap::new(), &mut articles);
+
+        assert_eq!(sections.iter().map(|section| section.directory.as_str()).collect::<Vec<_>>(), ["", "blog", "blog/rust"]);
+        let (root, blog, rust) = (section(&sections, ""), section(&sections, "blog"), section(&sections, "blog/rust"));
+        assert_eq!((root.title.as_str(), root.path.as_str()), ("Test", "/"));
+        assert_eq!((rust.title.as_str(), rust.path.as_str()), ("rust", "/blog/rust/"));
+        assert_eq!(sources(&articles, root), ["about.md"]);
+        assert!(sources(&articles, blog).is_empty());
+        assert_eq!(rust.parent, Some(1));
+        assert_eq!(blog.subsections, [2]);
+        assert_eq!(rust.ancestors.iter().map(|link| link.title.as_str()).collect::<Vec<_>>(), ["blog"]);
+        assert_eq!(articles[1].ancestors.iter().map(|link| link.path.as_str()).collect::<Vec<_>>(), ["/blog/", "/blog/rust/"]);
+    }
+
+    #[test]
+    fn sorts_pages_and_links_neighbours() {
+        let mut articles = [
+            article("blog/old.md", "2023-01-01T00:00:00Z"),
+            article("blog/new.md", "2024-01-01T00:00:00Z"),
+            article("blog/middle.md", "2023-06-01T00:00:00Z"),
+        ];
+        let sections = build_sections(&config(), &BTreeMap::new(), &mut articles);
+        assert_eq!(sources(&articles, section(&sections, "blog")), ["blog/new.md", "blog/middle.md", "blog/old.md"]);
+        assert_eq!(articles[2].previous.as_ref().map(|link| link.title.as_str()), Some("blog/new.md"));
+        assert_eq!(articles[2].next.as_ref().map(|link| link.title.as_str()), Some("blog/old.md"));
+        assert_eq!((articles[1].previous.as_ref(), articles[0].next.as_ref()), (None, None));
+
+        let indexes = BTreeMap::from([index("blog", r#"sort_by = "title""#)]);
+        let sections = build_sections(&config(), &indexes, &mut articles);
+        assert_eq!(sources(&articles, section(&sections, "blog")), ["blog/middle.md", "blog/new.md", "blog/old.md"]);
+    }
+
+    #[test]
+    fn transparent_sections_lift_pages_into_their_parent() {
+        let mut articles = [
+            article("blog/intro.md", "2023-01-01T00:00:00Z"),
+            article("blog/2024/latest.md", "2024-01-01T00:00:00Z"),
+        ];
+        let indexes = BTreeMap::from([index("blog/2024", "transparent = true")]);
+        let sections = build_sections(&config(), &indexes, &mut articles);
+        assert_eq!(sources(&articles, section(&sections, "blog")), ["blog/2024/latest.md", "blog/intro.md"]);
+        assert_eq!(sources(&articles, section(&sections, "blog/2024")), ["blog/2024/latest.md"]);
+    }
+
+    #[test]
+    fn inherits_page_templates_and_orders_subsections() {
+        let mut articles = [
+            article("docs/b/page.md", "2024-01-01T00:00:00Z"),
+            article("docs/a/page.md", "2024-01-01T00:00:00Z"),
+            article("docs/c/page.md", "2024-01-01T00:00:00Z"),
+        ];
+        let indexes = BTreeMap::from([
+            index("docs", r#"page_template = "doc.html""#),
+            index("docs/c", "weight = -1"),
+        ]);
+        let sections = build_sections(&config(), &indexes, &mut articles);
+        let docs = section(&sections, "docs");
+        assert_eq!(docs.subsections.iter().map(|index| sections[*index].title.as_str()).collect::<Vec<_>>(), ["c", "a", "b"]);
+        assert!(articles.iter().all(|article| article.template.as_deref() == Some("doc.html")));
+    }
+}
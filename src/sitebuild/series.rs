@@ -0,0 +1,228 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use chrono::{DateTime, FixedOffset};
+use itertools::Itertools;
+use miette::WrapErr;
+use serde::Serialize;
+use tracing::warn;
+use ilgi_core::error::IResult;
+use crate::config::IlgiConfig;
+use crate::file_ops::write_output;
+use crate::sitebuild::absolute_url;
+use crate::sitebuild::article::Article;
+use crate::sitebuild::links::ArticleLink;
+use crate::sitebuild::permalink::styled_path;
+use crate::sitebuild::render::{base_context, output_file, render_template_or};
+use crate::sitebuild::sitemap::SitemapEntry;
+use crate::sitebuild::slug::{normalize_term, url_slug};
+use crate::sitebuild::Site;
+use crate::theme::Theme;
+
+pub const SERIES_TEMPLATE: &str = "series.html";
+
+const DEFAULT_SERIES_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html lang="{{ site.language }}">
+<head><meta charset="utf-8"><title>{{ series.name }} - {{ site.title }}</title></head>
+<body>
+<h1>{{ series.name }}</h1>
+<ol>{% for part in series.parts %}<li value="{{ part.position }}"><a href="{{ part.permalink }}">{{ part.title }}</a></li>{% endfor %}</ol>
+</body>
+</html>
+"#;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SeriesPart {
+    #[serde(flatten)]
+    pub link: ArticleLink,
+    pub position: usize,
+    pub order: Option<u32>,
+    pub date: Option<DateTime<FixedOffset>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ArticleSeries {
+    pub name: String,
+    pub path: String,
+    pub permalink: String,
+    pub position: usize,
+    pub total: usize,
+    pub parts: Vec<SeriesPart>,
+    pub previous: Option<SeriesPart>,
+    pub next: Option<SeriesPart>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Series {
+    pub name: String,
+    pub slug: String,
+    pub language: String,
+    pub path: String,
+    pub permalink: String,
+    pub parts: Vec<SeriesPart>,
+    #[serde(skip)]
+    pub pages: Vec<usize>,
+}
+
+fn check_order(series: &str, language: &str, articles: &[Article], pages: &[usize]) -> Vec<String> {
+    let mut problems = vec![];
+    let orders = pages.iter().filter_map(|index| articles[*index].series_order).collect::<Vec<_>>();
+    if orders.is_empty() {
+        return problems;
+    }
+    for index in pages.iter().filter(|index| articles[**index].series_order.is_none()) {
+        problems.push(format!("{} is part of the \"{series}\" series ({language}) but has no series_order", articles[*index].source));
+    }
+    for (order, group) in &pages.iter().filter(|index| articles[**index].series_order.is_some()).group_by(|index| articles[**index].series_order) {
+        let sources = group.map(|index| articles[*index].source.as_str()).collect::<Vec<_>>();
+        if sources.len() > 1 {
+            problems.push(format!("{} share series_order {} in the \"{series}\" series ({language})", sources.join(", "), order.unwrap_or_default()));
+        }
+    }
+    let first = orders.iter().min().copied().unwrap_or_default().min(1);
+    let missing = (first..=orders.iter().max().copied().unwrap_or_default())
+        .filter(|order| !orders.contains(order))
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        problems.push(format!("the \"{series}\" series ({language}) has no part {}", missing.iter().join(", ")));
+    }
+    problems
+}
+
+pub fn collect_series(config: &IlgiConfig, articles: &mut [Article]) -> Vec<Series> {
+    let mut grouped = BTreeMap::<(String, String), Vec<usize>>::new();
+    for (index, article) in articles.iter().enumerate() {
+        if let Some(name) = &article.series_name {
+            grouped.entry((article.language.clone(), normalize_term(name))).or_default().push(index);
+        }
+    }
+
+    let mut collected = vec![];
+    for ((language, _), mut pages) in grouped {
+        pages.sort_by(|a, b| {
+            let (a, b) = (&articles[*a], &articles[*b]);
+            (a.series_order.is_none(), a.series_order, a.date, &a.source).cmp(&(b.series_order.is_none(), b.series_order, b.date, &b.source))
+        });
+        let name = articles[pages[0]].series_name.clone().unwrap_or_default().trim().to_string();
+        for problem in check_order(&name, &language, articles, &pages) {
+            warn!("{problem}");
+        }
+
+        let slug = url_slug(&config.build.permalinks, &name);
+        let path = styled_path(config, &format!("{}/{}/{slug}/", config.language_prefix(&language), config.build.series.path.trim_matches('/')));
+        let parts = pages.iter()
+            .enumerate()
+            .map(|(position, index)| SeriesPart {
+                link: articles[*index].link(),
+                position: position + 1,
+                order: articles[*index].series_order,
+                date: articles[*index].date,
+            })
+            .collect::<Vec<_>>();
+
+        let permalink = absolute_url(config, &path);
+        for (position, index) in pages.iter().enumerate() {
+            articles[*index].series = Some(ArticleSeries {
+                name: name.clone(),
+                path: path.clone(),
+                permalink: permalink.clone(),
+                position: position + 1,
+                total: parts.len(),
+                previous: position.checked_sub(1).map(|previous| parts[previous].clone()),
+                next: parts.get(position + 1).cloned(),
+                parts: parts.clone(),
+            });
+        }
+        collected.push(Series { name, slug, language, path, permalink, parts, pages });
+    }
+    collected
+}
+
+pub fn sitemap_entries<'a>(site: &'a Site) -> impl Iterator<Item = SitemapEntry> + 'a {
+    site.series.iter().map(|series| SitemapEntry {
+        loc: series.permalink.clone(),
+        lastmod: series.pages.iter().filter_map(|index| site.last_modified(&site.articles[*index])).max(),
+        alternates: vec![],
+    })
+}
+
+pub async fn write_series(config: &IlgiConfig, theme: &Theme, site: &Site, output: &Path) -> IResult<()> {
+    for series in &site.series {
+        let mut context = base_context(config, &series.language);
+        context.insert("series", series);
+        context.insert("pages", &series.pages.iter().map(|index| &site.articles[*index]).collect::<Vec<_>>());
+        let html = render_template_or(theme, SERIES_TEMPLATE, DEFAULT_SERIES_TEMPLATE, &context)
+            .wrap_err_with(|| format!("while rendering the \"{}\" series", series.name))?;
+        write_output(output, output_file(&series.path), html).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{test_config, SlugStyle, UrlStyle};
+    use super::*;
+
+    fn article(source: &str, series: &str, order: Option<u32>) -> Article {
+        Article {
+            source: source.to_string(),
+            title: source.to_string(),
+            language: "en".to_string(),
+            series_name: Some(series.to_string()),
+            series_order: order,
+            ..Default::default()
+        }
+    }
+
+    fn config() -> IlgiConfig {
+        let mut config = test_config();
+        config.build.series.path = "series".to_string();
+        config.build.permalinks.style = UrlStyle::TrailingSlash;
+        config.build.permalinks.slugify = SlugStyle::Unicode;
+        config
+    }
+
+    #[test]
+    fn orders_parts_and_links_neighbours() {
+        let mut articles = [
+            article("two.md", "Rust Basics", Some(2)),
+            article("one.md", " rust basics ", Some(1)),
+            article("other.md", "Async", None),
+        ];
+        let series = collect_series(&config(), &mut articles);
+
+        assert_eq!(series.iter().map(|series| series.name.as_str()).collect::<Vec<_>>(), ["Async", "rust basics"]);
+        assert_eq!(series[1].path, "/series/rust-basics/");
+        assert_eq!(series[1].parts.iter().map(|part| part.link.title.as_str()).collect::<Vec<_>>(), ["one.md", "two.md"]);
+        let second = articles[0].series.as_ref().unwrap();
+        assert_eq!((second.position, second.total), (2, 2));
+        assert_eq!(second.previous.as_ref().map(|part| part.link.title.as_str()), Some("one.md"));
+        assert_eq!(second.next, None);
+    }
+
+    #[test]
+    fn reports_gaps_duplicates_and_missing_orders() {
+        let articles = [
+            article("one.md", "Guide", Some(1)),
+            article("three.md", "Guide", Some(3)),
+            article("also-three.md", "Guide", Some(3)),
+            article("extra.md", "Guide", None),
+            article("six.md", "Guide", Some(6)),
+        ];
+        assert_eq!(check_order("Guide", "en", &articles, &[0, 2, 1, 4, 3]), [
+            "extra.md is part of the \"Guide\" series (en) but has no series_order",
+            "also-three.md, three.md share series_order 3 in the \"Guide\" series (en)",
+            "the \"Guide\" series (en) has no part 2, 4, 5",
+        ]);
+    }
+
+    #[test]
+    fn accepts_complete_and_unordered_series() {
+        let articles = [
+            article("one.md", "Guide", Some(1)),
+            article("two.md", "Guide", Some(2)),
+            article("loose.md", "Notes", None),
+        ];
+        assert!(check_order("Guide", "en", &articles, &[0, 1]).is_empty());
+        assert!(check_order("Notes", "en", &articles, &[2]).is_empty());
+    }
+}
@@ -0,0 +1,80 @@
+use std::fmt::Write;
+use chrono::{DateTime, FixedOffset, SecondsFormat};
+use crate::config::IlgiConfig;
+use crate::sitebuild::absolute_url;
+
+pub const FILE_NAME: &str = "sitemap.xml";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SitemapEntry {
+    pub loc: String,
+    pub lastmod: Option<DateTime<FixedOffset>>,
+    pub alternates: Vec<(String, String)>,
+}
+
+pub fn render_sitemaps(config: &IlgiConfig, entries: &[SitemapEntry]) -> Vec<(String, String)> {
+    let max_urls = config.build.sitemap.max_urls.max(1);
+    if entries.len() <= max_urls {
+        return vec![(FILE_NAME.to_string(), render_urlset(entries))]
+    }
+
+    let mut files = entries.chunks(max_urls)
+        .enumerate()
+        .map(|(index, chunk)| (format!("sitemap-{}.xml", index + 1), render_urlset(chunk), chunk.iter().filter_map(|entry| entry.lastmod).max()))
+        .collect::<Vec<_>>();
+
+    let mut index = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>
+<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+"#);
+    for (name, _, lastmod) in &files {
+        index.push_str("<sitemap><loc>");
+        index.push_str(&escape_xml(&absolute_url(config, name)));
+        index.push_str("</loc>");
+        if let Some(lastmod) = lastmod {
+            let _ = write!(index, "<lastmod>{}</lastmod>", lastmod.to_rfc3339_opts(SecondsFormat::Secs, true));
+        }
+        index.push_str("</sitemap>\n");
+    }
+    index.push_str("</sitemapindex>\n");
+
+    let mut output = files.drain(..)
+        .map(|(name, urlset, _)| (name, urlset))
+        .collect::<Vec<_>>();
+    output.push((FILE_NAME.to_string(), index));
+    output
+}
+
+fn render_urlset(entries: &[SitemapEntry]) -> String {
+    let mut urlset = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9" xmlns:xhtml="http://www.w3.org/1999/xhtml">
+"#);
+    for entry in entries {
+        urlset.push_str("<url><loc>");
+        urlset.push_str(&escape_xml(&entry.loc));
+        urlset.push_str("</loc>");
+        if let Some(lastmod) = entry.lastmod {
+            let _ = write!(urlset, "<lastmod>{}</lastmod>", lastmod.to_rfc3339_opts(SecondsFormat::Secs, true));
+        }
+        for (language, href) in &entry.alternates {
+            let _ = write!(urlset, r#"<xhtml:link rel="alternate" hreflang="{}" href="{}"/>"#, escape_xml(language), escape_xml(href));
+        }
+        urlset.push_str("</url>\n");
+    }
+    urlset.push_str("</urlset>\n");
+    urlset
+}
+
+pub fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,61 @@
+use deunicode::deunicode;
+use unicode_normalization::UnicodeNormalization;
+use crate::config::{Permalinks, SlugStyle};
+
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::with_capacity(text.len());
+    let mut separator = false;
+    for c in text.nfc().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            if separator && !slug.is_empty() {
+                slug.push('-');
+            }
+            separator = false;
+            slug.push(c);
+        } else {
+            separator = true;
+        }
+    }
+    slug
+}
+
+pub fn url_slug(config: &Permalinks, text: &str) -> String {
+    match config.slugify {
+        SlugStyle::Unicode => slugify(text),
+        SlugStyle::Transliterate => slugify(&deunicode(text)),
+    }
+}
+
+pub fn normalize_term(term: &str) -> String {
+    term.trim().nfkc().flat_map(char::to_lowercase).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::test_config;
+    use super::*;
+
+    #[test]
+    fn collapses_punctuation_into_single_dashes() {
+        assert_eq!(slugify("  Hello, World!  "), "hello-world");
+        assert_eq!(slugify("Rust -- 2024 edition"), "rust-2024-edition");
+        assert_eq!(slugify("?!"), "");
+    }
+
+    #[test]
+    fn keeps_unicode_letters_unless_transliterating() {
+        let mut permalinks = test_config().build.permalinks;
+        permalinks.slugify = SlugStyle::Unicode;
+        assert_eq!(url_slug(&permalinks, "Café Über"), "café-über");
+        assert_eq!(url_slug(&permalinks, "한국어 글"), "한국어-글");
+
+        permalinks.slugify = SlugStyle::Transliterate;
+        assert_eq!(url_slug(&permalinks, "Café Über"), "cafe-uber");
+    }
+
+    #[test]
+    fn normalizes_composed_and_decomposed_forms_alike() {
+        assert_eq!(slugify("Cafe\u{301}"), slugify("Caf\u{e9}"));
+        assert_eq!(normalize_term(" Ｒｕｓｔ "), "rust");
+    }
+}
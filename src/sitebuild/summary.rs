@@ -0,0 +1,307 @@
+use serde::Serialize;
+use crate::formatting::is_cjk;
+
+pub const MORE_ANCHOR: &str = "<span id=\"more\"></span>";
+
+const VOID_ELEMENTS: [&str; 14] = ["area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source", "track", "wbr"];
+const HIDDEN_ELEMENTS: [&str; 4] = ["nav", "script", "style", "template"];
+const ELLIPSIS: &str = "…";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HtmlToken<'a> {
+    Text(&'a str),
+    Comment(&'a str),
+    Open { raw: &'a str, name: String, void: bool },
+    Close { raw: &'a str, name: String },
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Summary {
+    pub html: String,
+    pub text: String,
+    pub has_more: bool,
+}
+
+fn tag_end(html: &str) -> Option<usize> {
+    let mut quote = None;
+    for (index, character) in html.char_indices() {
+        match (quote, character) {
+            (Some(open), character) if character == open => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(character),
+            (None, '>') => return Some(index + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+pub fn html_tokens(html: &str) -> Vec<HtmlToken<'_>> {
+    let mut tokens = vec![];
+    let mut rest = html;
+    while !rest.is_empty() {
+        let starts_tag = |text: &str| {
+            text.starts_with('<') && text[1..].starts_with(|character: char| character.is_ascii_alphabetic() || character == '/' || character == '!')
+        };
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            let end = comment.find("-->").map_or(rest.len(), |end| end + 7);
+            tokens.push(HtmlToken::Comment(&rest[..end]));
+            rest = &rest[end..];
+        } else if let Some(end) = starts_tag(rest).then(|| tag_end(rest)).flatten() {
+            let raw = &rest[..end];
+            let closing = raw.starts_with("</");
+            let name = raw.trim_start_matches(['<', '/'])
+                .split(|character: char| !character.is_ascii_alphanumeric() && character != '-' && character != '!')
+                .next()
+                .unwrap_or_default()
+                .to_ascii_lowercase();
+            tokens.push(match closing {
+                true => HtmlToken::Close { raw, name },
+                false => HtmlToken::Open {
+                    raw,
+                    void: raw.ends_with("/>") || name.starts_with('!') || VOID_ELEMENTS.contains(&name.as_str()),
+                    name,
+                },
+            });
+            rest = &rest[end..];
+        } else {
+            let end = rest.char_indices().skip(1).find(|(_, character)| *character == '<').map_or(rest.len(), |(end, _)| end);
+            tokens.push(HtmlToken::Text(&rest[..end]));
+            rest = &rest[end..];
+        }
+    }
+    tokens
+}
+
+fn is_hidden(name: &str, raw: &str) -> bool {
+    HIDDEN_ELEMENTS.contains(&name) || raw.contains("aria-hidden=\"true\"")
+}
+
+pub fn visible_tokens(html: &str) -> Vec<HtmlToken<'_>> {
+    let mut depth = 0usize;
+    let mut hidden = None;
+    html_tokens(html).into_iter()
+        .filter(|token| {
+            match token {
+                HtmlToken::Open { raw, name, void: false } => {
+                    depth += 1;
+                    if hidden.is_none() && is_hidden(name, raw) {
+                        hidden = Some(depth);
+                    }
+                }
+                HtmlToken::Close { .. } => {
+                    depth = depth.saturating_sub(1);
+                    if hidden == Some(depth + 1) {
+                        hidden = None;
+                        return false;
+                    }
+                }
+                _ => {}
+            }
+            hidden.is_none()
+        })
+        .collect()
+}
+
+pub fn is_more_marker(comment: &str) -> bool {
+    comment.trim_start_matches("<!--").trim_end_matches("-->").trim().eq_ignore_ascii_case("more")
+}
+
+fn close(html: &mut String, open: &[String]) {
+    for name in open.iter().rev() {
+        html.push_str(&format!("</{name}>"));
+    }
+}
+
+fn word_cut(text: &str, limit: usize) -> Result<usize, usize> {
+    let mut count = 0;
+    let mut in_word = false;
+    for (index, character) in text.char_indices() {
+        let starts_unit = match character {
+            character if is_cjk(character) => {
+                in_word = false;
+                true
+            }
+            character if character.is_whitespace() => {
+                in_word = false;
+                false
+            }
+            _ => !std::mem::replace(&mut in_word, true),
+        };
+        if starts_unit {
+            if count == limit {
+                return Ok(index);
+            }
+            count += 1;
+        }
+    }
+    Err(count)
+}
+
+pub fn plain_text(html: &str) -> String {
+    let mut text = String::new();
+    for token in visible_tokens(html) {
+        match token {
+            HtmlToken::Text(content) => text.push_str(&decode_entities(content)),
+            HtmlToken::Open { .. } | HtmlToken::Close { .. } if !text.ends_with(char::is_whitespace) => text.push(' '),
+            _ => {}
+        }
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+pub fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..].find(';').map(|end| &rest[1..end + 1]).filter(|entity| entity.len() <= 8);
+        let character = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            entity => match entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+                None => entity.strip_prefix('#').and_then(|decimal| decimal.parse().ok()).and_then(char::from_u32),
+            },
+        });
+        match (entity, character) {
+            (Some(entity), Some(character)) => {
+                decoded.push(character);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+pub fn summarize(content: &str, words: usize) -> (String, Summary) {
+    let tokens = visible_tokens(content);
+    let marker = tokens.iter().find_map(|token| match token {
+        HtmlToken::Comment(comment) if is_more_marker(comment) => Some(*comment),
+        _ => None,
+    });
+    let mut open = Vec::<String>::new();
+    let mut html = String::new();
+    let mut remaining = words;
+
+    for (index, token) in tokens.iter().enumerate() {
+        match token {
+            HtmlToken::Comment(comment) if Some(*comment) == marker => {
+                close(&mut html, &open);
+                let text = plain_text(&html);
+                return (content.replacen(comment, MORE_ANCHOR, 1), Summary { html, text, has_more: true });
+            }
+            HtmlToken::Text(text) if marker.is_none() && words > 0 => {
+                let cut = word_cut(text, remaining).or_else(|count| {
+                    remaining -= count;
+                    let more = tokens[index + 1..].iter().any(|token| matches!(token, HtmlToken::Text(text) if word_cut(text, 0).is_ok()));
+                    match remaining == 0 && count > 0 && more {
+                        true => Ok(text.len()),
+                        false => Err(()),
+                    }
+                });
+                match cut {
+                    Ok(cut) => {
+                        html.push_str(text[..cut].trim_end());
+                        html.push_str(ELLIPSIS);
+                        close(&mut html, &open);
+                        let text = plain_text(&html);
+                        return (content.to_string(), Summary { html, text, has_more: true });
+                    }
+                    Err(()) => html.push_str(text),
+                }
+            }
+            HtmlToken::Text(raw) | HtmlToken::Comment(raw) => html.push_str(raw),
+            HtmlToken::Open { raw, name, void } => {
+                html.push_str(raw);
+                if !void {
+                    open.push(name.clone());
+                }
+            }
+            HtmlToken::Close { raw, name } => {
+                html.push_str(raw);
+                if let Some(position) = open.iter().rposition(|open| open == name) {
+                    open.truncate(position);
+                }
+            }
+        }
+    }
+    (content.to_string(), Summary { html, text: plain_text(content), has_more: false })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(content: &str, words: usize) -> Summary {
+        summarize(content, words).1
+    }
+
+    #[test]
+    fn tokenizes_tags_comments_and_text() {
+        assert_eq!(html_tokens(r#"<a title="1 > 0">x</a><!-- c --><br/>a < b"#), [
+            HtmlToken::Open { raw: r#"<a title="1 > 0">"#, name: "a".to_string(), void: false },
+            HtmlToken::Text("x"),
+            HtmlToken::Close { raw: "</a>", name: "a".to_string() },
+            HtmlToken::Comment("<!-- c -->"),
+            HtmlToken::Open { raw: "<br/>", name: "br".to_string(), void: true },
+            HtmlToken::Text("a "),
+            HtmlToken::Text("< b"),
+        ]);
+    }
+
+    #[test]
+    fn counts_words_and_cjk_characters() {
+        assert_eq!(word_cut("one two three", 2), Ok(8));
+        assert_eq!(word_cut("  one two ", 5), Err(2));
+        assert_eq!(word_cut("日本語です", 2), Ok(6));
+        assert_eq!(word_cut("Rust로 만든 블로그", 3), Ok(11));
+    }
+
+    #[test]
+    fn cuts_inside_text_and_closes_open_tags() {
+        let summary = summary(r#"<p>one <em title="not counted either">two three</em> four</p><p>five</p>"#, 2);
+        assert_eq!(summary.html, r#"<p>one <em title="not counted either">two…</em></p>"#);
+        assert_eq!(summary.text, "one two…");
+        assert!(summary.has_more);
+    }
+
+    #[test]
+    fn cuts_between_elements_without_splitting_tags() {
+        let summary = summary(r#"<p>one two</p><p><a href="/three/">three</a></p>"#, 2);
+        assert_eq!(summary.html, "<p>one two…</p>");
+        assert_eq!(html_tokens(&summary.html).iter().filter(|token| matches!(token, HtmlToken::Open { .. })).count(), 1);
+    }
+
+    #[test]
+    fn keeps_short_content_whole() {
+        let content = "<p>one two</p><script>three four</script>";
+        let summary = summary(content, 2);
+        assert_eq!((summary.html.as_str(), summary.text.as_str(), summary.has_more), ("<p>one two</p>", "one two", false));
+    }
+
+    #[test]
+    fn stops_at_the_more_marker() {
+        let (content, summary) = summarize("<p>intro</p>\n<!-- More -->\n<p>rest</p>", 1);
+        assert_eq!(summary.html, "<p>intro</p>\n");
+        assert!(summary.has_more);
+        assert_eq!(content, format!("<p>intro</p>\n{MORE_ANCHOR}\n<p>rest</p>"));
+    }
+
+    #[test]
+    fn decodes_entities_in_plain_text() {
+        assert_eq!(plain_text("<p>Fish &amp; chips&#33; &#x1F980; &bogus;</p><nav>menu</nav>"), "Fish & chips! 🦀 &bogus;");
+    }
+}
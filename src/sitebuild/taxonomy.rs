@@ -0,0 +1,195 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use miette::WrapErr;
+use serde::Serialize;
+use tracing::warn;
+use ilgi_core::error::{IlgiError, IResult};
+use crate::config::{IlgiConfig, Taxonomy as TaxonomyConfig};
+use crate::file_ops::write_output;
+use crate::sitebuild::absolute_url;
+use crate::sitebuild::article::Article;
+use crate::sitebuild::feed::write_feeds;
+use crate::sitebuild::pagination::paginate;
+use crate::sitebuild::permalink::styled_path;
+use crate::sitebuild::render::{base_context, output_file, render_template};
+use crate::sitebuild::sitemap::SitemapEntry;
+use crate::sitebuild::slug::{normalize_term, url_slug};
+use crate::sitebuild::Site;
+use crate::theme::Theme;
+
+pub const LIST_TEMPLATE: &str = "taxonomy_list.html";
+pub const TERM_TEMPLATE: &str = "taxonomy_term.html";
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TaxonomyTerm<'a> {
+    pub name: String,
+    pub slug: String,
+    pub path: String,
+    pub permalink: String,
+    pub count: usize,
+    #[serde(skip)]
+    pub articles: Vec<&'a Article>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Taxonomy<'a> {
+    pub name: String,
+    pub language: String,
+    pub path: String,
+    pub permalink: String,
+    pub terms: Vec<TaxonomyTerm<'a>>,
+    #[serde(skip)]
+    pub config: TaxonomyConfig,
+}
+
+impl<'a> Taxonomy<'a> {
+    pub fn collect(config: &IlgiConfig, language: &str, taxonomy: TaxonomyConfig, articles: &[&'a Article]) -> IResult<Taxonomy<'a>> {
+        let directory = format!("{}/{}", config.language_prefix(language), url_slug(&config.build.permalinks, &taxonomy.name));
+        let path = styled_path(config, &format!("{directory}/"));
+
+        let mut grouped = BTreeMap::<String, (BTreeSet<String>, Vec<&'a Article>)>::new();
+        for &article in articles {
+            for term in article.terms(&taxonomy.name) {
+                let (spellings, tagged) = grouped.entry(normalize_term(term)).or_default();
+                spellings.insert(term.trim().to_string());
+                if !tagged.iter().any(|other| std::ptr::eq(*other, article)) {
+                    tagged.push(article);
+                }
+            }
+        }
+
+        let mut slugs = BTreeMap::<String, String>::new();
+        let mut terms = vec![];
+        for (spellings, articles) in grouped.into_values() {
+            let Some(name) = spellings.iter().next().cloned() else {
+                continue
+            };
+            if spellings.len() > 1 {
+                warn!("{} terms {:?} differ only in case or normalisation and were merged into \"{name}\"", taxonomy.name, spellings);
+            }
+
+            let slug = url_slug(&config.build.permalinks, &name);
+            if slug.is_empty() {
+                return Err(IlgiError::EmptyTermSlug { taxonomy: taxonomy.name.clone(), term: name }.into());
+            }
+            if let Some(existing) = slugs.insert(slug.clone(), name.clone()) {
+                return Err(IlgiError::DuplicateTermSlug { taxonomy: taxonomy.name.clone(), existing, term: name, slug }.into());
+            }
+
+            let term_path = styled_path(config, &format!("{directory}/{slug}/"));
+            terms.push(TaxonomyTerm {
+                permalink: absolute_url(config, &term_path),
+                path: term_path,
+                count: articles.len(),
+                name,
+                slug,
+                articles,
+            });
+        }
+
+        Ok(Taxonomy {
+            name: taxonomy.name.clone(),
+            language: language.to_string(),
+            permalink: absolute_url(config, &path),
+            path,
+            terms,
+            config: taxonomy,
+        })
+    }
+
+    pub fn sitemap_entries<'b>(&'b self, site: &'b Site) -> impl Iterator<Item = SitemapEntry> + 'b {
+        let list = SitemapEntry {
+            loc: self.permalink.clone(),
+            lastmod: self.terms.iter()
+                .flat_map(|term| term.articles.iter().filter_map(|article| site.last_modified(article)))
+                .max(),
+            alternates: vec![],
+        };
+        std::iter::once(list).chain(self.terms.iter().map(|term| SitemapEntry {
+            loc: term.permalink.clone(),
+            lastmod: term.articles.iter().filter_map(|article| site.last_modified(article)).max(),
+            alternates: vec![],
+        }))
+    }
+}
+
+pub fn collect_taxonomies<'a>(config: &IlgiConfig, language: &str, articles: &[&'a Article]) -> IResult<Vec<Taxonomy<'a>>> {
+    config.build.taxonomies()
+        .into_iter()
+        .map(|taxonomy| Taxonomy::collect(config, language, taxonomy, articles))
+        .collect()
+}
+
+pub async fn write_taxonomies(config: &IlgiConfig, theme: &Theme, output: &Path, taxonomies: &[Taxonomy<'_>]) -> IResult<()> {
+    for taxonomy in taxonomies {
+        let mut context = base_context(config, &taxonomy.language);
+        context.insert("taxonomy", taxonomy);
+        let html = render_template(theme, LIST_TEMPLATE, &context)
+            .wrap_err_with(|| format!("while rendering the {} taxonomy", taxonomy.name))?;
+        write_output(output, output_file(&taxonomy.path), html).await?;
+
+        for term in &taxonomy.terms {
+            for paginator in paginate(config, &term.path, &term.articles, taxonomy.config.paginate_by) {
+                let mut context = base_context(config, &taxonomy.language);
+                context.insert("taxonomy", taxonomy);
+                context.insert("term", term);
+                context.insert("paginator", &paginator);
+                let html = render_template(theme, TERM_TEMPLATE, &context)
+                    .wrap_err_with(|| format!("while rendering {} term \"{}\"", taxonomy.name, term.name))?;
+                write_output(output, output_file(&paginator.path), html).await?;
+            }
+
+            if taxonomy.config.feed {
+                write_feeds(config, output, &term.path, &taxonomy.language, term.articles.iter().copied()).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{test_config, SlugStyle};
+    use super::*;
+
+    fn tagged(source: &str, tags: &[&str]) -> Article {
+        Article {
+            source: source.to_string(),
+            language: "en".to_string(),
+            taxonomies: [("tags".to_string(), tags.iter().map(|tag| tag.to_string()).collect())].into_iter().collect(),
+            ..Default::default()
+        }
+    }
+
+    fn tags<'a>(config: &IlgiConfig, articles: &[&'a Article]) -> IResult<Taxonomy<'a>> {
+        Taxonomy::collect(config, "en", TaxonomyConfig::new("tags"), articles)
+    }
+
+    #[test]
+    fn merges_spellings_of_a_term() {
+        let (first, second) = (tagged("a.md", &["Rust", "web"]), tagged("b.md", &["rust "]));
+        let taxonomy = tags(&test_config(), &[&first, &second]).unwrap();
+        let terms = taxonomy.terms.iter().map(|term| (term.name.as_str(), term.path.as_str(), term.count)).collect::<Vec<_>>();
+        assert_eq!(terms, [("Rust", "/tags/rust/", 2), ("web", "/tags/web/", 1)]);
+    }
+
+    #[test]
+    fn rejects_terms_without_a_slug() {
+        let article = tagged("a.md", &["rust", "?!"]);
+        let error = tags(&test_config(), &[&article]).unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(IlgiError::EmptyTermSlug { term, .. }) if term == "?!"), "{error:?}");
+
+        let article = tagged("a.md", &["\u{1F980}"]);
+        assert!(matches!(tags(&test_config(), &[&article]).unwrap_err().downcast_ref(), Some(IlgiError::EmptyTermSlug { .. })));
+        let mut config = test_config();
+        config.build.permalinks.slugify = SlugStyle::Transliterate;
+        assert_eq!(tags(&config, &[&article]).unwrap().terms[0].path, "/tags/crab/");
+    }
+
+    #[test]
+    fn rejects_terms_sharing_a_slug() {
+        let article = tagged("a.md", &["C++", "C"]);
+        let error = tags(&test_config(), &[&article]).unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(IlgiError::DuplicateTermSlug { slug, .. }) if slug == "c"), "{error:?}");
+    }
+}
@@ -5,7 +5,6 @@ use std::str::{from_utf8, from_utf8_unchecked};
 use std::sync::{Arc};
 use dashmap::DashMap;
 use fallible_iterator::FallibleIterator;
-use ignore::{WalkBuilder};
 use memmap2::Mmap;
 use relative_path::RelativePath;
 use rhai::{AST, Engine, Scope};
@@ -16,13 +15,16 @@ use ilgi_core::error::IResult;
 use itertools::{process_results};
 use lightningcss::stylesheet::{MinifyOptions, ParserOptions, PrinterOptions, StyleSheet};
 use lightningcss::targets::Browsers;
-use miette::Report;
+use miette::{IntoDiagnostic, Report};
 use rsass::output::{Format, Style};
 use tera::Tera;
 use ilgi_core::theme::ThemeDefinition;
 use upon::{Engine as UponEngine, Value};
 use crate::config::{CssStyle, IlgiConfig};
-use crate::file_ops::{add_hash_filename, optimize_static_file};
+use crate::file_ops::{add_hash_filename, load_dir, optimize_static_file};
+use crate::formatting;
+use crate::i18n::{register_rhai, register_tera, register_upon, Translations};
+use crate::sitebuild::highlight::{Highlighter, STYLESHEET};
 
 #[derive(Clone, Debug, Default, PartialOrd, PartialEq)]
 pub struct DiskTheme {
@@ -36,9 +38,12 @@ pub struct DiskTheme {
     pub shortcodes: DashMap<String, Mmap>,
     pub rhai_functions: DashMap<String, Mmap>,
     pub sass: DashMap<String, Mmap>,
+    pub i18n: DashMap<String, Mmap>,
+    pub syntaxes: DashMap<String, Mmap>,
+    pub highlight_themes: DashMap<String, Mmap>,
 }
 
-#[derive(Clone, Debug, Default, PartialOrd, PartialEq)]
+#[derive(Clone, Debug)]
 pub struct Theme {
     pub definition: ThemeDefinition,
     pub statics: Arc<DashMap<String, Box<dyn AsRef<[u8]>>>>,
@@ -47,6 +52,8 @@ pub struct Theme {
     pub rhai_engine: Engine,
     pub rhai_functions: Arc<DashMap<String, AST>>,
     pub sass: Arc<DashMap<String, String>>,
+    pub translations: Arc<Translations>,
+    pub highlighter: Arc<Highlighter>,
 }
 
 impl DiskTheme {
@@ -54,7 +61,25 @@ impl DiskTheme {
     pub async fn load(self, config: &IlgiConfig) -> IResult<Theme> {
         //
 
+        let timezone = config.tz()?;
+        let utf8_sources = |files: DashMap<String, Mmap>| files.into_iter()
+            .map(|(name, data)| from_utf8(data.as_ref()).map(|source| (name, source.to_string())))
+            .collect::<Result<Vec<_>, _>>()
+            .into_diagnostic();
+
+        let translations = Arc::new(Translations::load(
+            self.definition.default_language.as_deref().unwrap_or(&config.default_language),
+            utf8_sources(self.i18n)?,
+        )?);
+        let highlighter = Arc::new(Highlighter::new(
+            &config.build.highlight,
+            utf8_sources(self.syntaxes)?,
+            utf8_sources(self.highlight_themes)?,
+        )?);
+
         let mut tera = Tera::default();
+        register_tera(&mut tera, translations.clone());
+        formatting::register_tera(&mut tera, timezone, &translations.default_language);
         tera.add_raw_templates(
             process_results(
                 self.templates.into_iter()
@@ -75,6 +100,8 @@ impl DiskTheme {
         let mut sass = Arc::new(
             process_results(
             self.sass.into_iter()
+                .map(|(n, m)| (n, m.to_vec()))
+                .chain(highlighter.stylesheet()?.map(|css| (STYLESHEET.to_string(), css.into_bytes())))
                 .map(|(n, m)| {
                     rsass::compile_scss(&m, Format { style: {
                         match config.build.css.style {
@@ -119,6 +146,7 @@ impl DiskTheme {
         )?.collect::<Result<DashMap<String, String>, Report>>()?);
 
         let mut engine = Engine::new();
+        register_rhai(&mut engine, translations.clone());
         let mut rhai_functions = Arc::new(
             process_results(
                 self.rhai_functions.into_iter().map(|(n, a)| {
@@ -133,6 +161,8 @@ impl DiskTheme {
         );
 
         let mut upon = UponEngine::new();
+        register_upon(&mut upon, translations.clone());
+        formatting::register_upon(&mut upon, timezone);
         let _ = process_results(
             self.runtime_templates.into_iter().map(|(s, n)| {
                 from_utf8(n.as_ref()).map(|x| (s, x))
@@ -230,6 +260,8 @@ impl DiskTheme {
                 rhai_engine: engine,
                 rhai_functions,
                 sass,
+                translations,
+                highlighter,
             }
         )
     }
@@ -257,6 +289,11 @@ pub async fn parse_theme(directory: impl AsRef<Path>, config: &IlgiConfig) -> IR
 
     let rhai_functions = map_dir_to_named_mem(path.clone() + "rhai")?.collect::<DashMap<_, _>>();
 
+    let i18n = map_optional_dir_to_named_mem(path.clone() + "i18n")?;
+
+    let syntaxes = map_optional_dir_to_named_mem(path.clone() + "highlight/syntaxes")?;
+    let highlight_themes = map_optional_dir_to_named_mem(path.clone() + "highlight/themes")?;
+
 
     Ok(
         DiskTheme {
@@ -270,24 +307,19 @@ pub async fn parse_theme(directory: impl AsRef<Path>, config: &IlgiConfig) -> IR
             shortcodes,
             rhai_functions,
             sass,
+            i18n,
+            syntaxes,
+            highlight_themes,
         }
     )
 }
 
-fn load_dir(path: impl AsRef<Path>) -> IResult<impl Iterator<Item = PathBuf>> {
-    Ok(
-        process_results(
-            WalkBuilder::new(path.as_ref()).add_custom_ignore_filename(".ilgi_ignore")
-                .build()
-                .into_iter()
-            ,
-            |file| {
-                file.map(|x| {
-                    x.into_path()
-                })
-            }
-        )?
-    )
+// Optional theme directories load as empty so themes written before they existed keep working.
+fn map_optional_dir_to_named_mem(path: impl AsRef<Path>) -> IResult<DashMap<String, Mmap>> {
+    match path.as_ref().is_dir() {
+        true => Ok(map_dir_to_named_mem(path)?.collect()),
+        false => Ok(DashMap::new()),
+    }
 }
 
 fn map_dir_to_named_mem(path: impl AsRef<Path>) -> IResult<impl Iterator<Item = (String, Mmap)>> {